@@ -10,7 +10,7 @@ use async_trait::async_trait;
 use console::{Style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde_json::{Value, json};
-use tokio::sync::{Mutex, Notify};
+use tokio::sync::Notify;
 
 use looper::{
     looper::Looper,
@@ -54,12 +54,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
         let theme = Theme::default();
         let mut spinner: Option<ProgressBar> = None;
 
-        while let Some(message) = ui_rx.recv().await {
+        while let Some(event) = ui_rx.recv().await {
             if let Some(sp) = spinner.take() {
                 sp.finish_and_clear();
             }
 
-            match message {
+            match event.message {
                 LooperToInterfaceMessage::Assistant(m) => {
                     print!("{}", m);
                     io::stdout().flush().ok();
@@ -71,15 +71,34 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 LooperToInterfaceMessage::ThinkingComplete => {
                     println!();
                 }
-                LooperToInterfaceMessage::ToolCall(name) => {
-                    spinner = Some(theme.tool_spinner(&name));
+                LooperToInterfaceMessage::ToolCallExecuting(call) => {
+                    spinner = Some(theme.tool_spinner(&call.name));
                 }
-                LooperToInterfaceMessage::ToolCallPending(_id) => {
-                    // TODO: Implement intelligent swap of tool calls based on id
+                LooperToInterfaceMessage::ToolCallInvalid(e) => {
+                    println!(
+                        "{}",
+                        theme.thinking.apply_to(format!(
+                            "[{}] invalid arguments: {}",
+                            e.name,
+                            e.errors.join("; ")
+                        ))
+                    );
                 }
+                LooperToInterfaceMessage::ToolCallStarted(_)
+                | LooperToInterfaceMessage::ToolCallArgsDelta(_)
+                | LooperToInterfaceMessage::ToolCallArgsComplete(_) => {}
                 LooperToInterfaceMessage::ToolCallComplete(_id) => {
                     // TODO: Handle tool call completion
                 }
+                LooperToInterfaceMessage::ServerToolCall(call) => {
+                    spinner = Some(theme.tool_spinner(&call.name));
+                }
+                LooperToInterfaceMessage::ServerToolResult(_) => {}
+                LooperToInterfaceMessage::Citation(c) => {
+                    if let Some(url) = c.url {
+                        println!("{}", theme.thinking.apply_to(format!("[source] {}", url)));
+                    }
+                }
                 LooperToInterfaceMessage::TurnComplete => {
                     println!("\n{}", theme.separator_line());
                     turn_done_tx.notify_one();
@@ -123,7 +142,7 @@ impl LooperTool for ReadFileTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let path = args["path"].as_str().unwrap_or("");
         match tokio::fs::read_to_string(path).await {
             Ok(content) => json!({ "path": path, "content": content }),
@@ -154,7 +173,7 @@ impl LooperTool for WriteFileTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let path = args["path"].as_str().unwrap_or("");
         let content = args["content"].as_str().unwrap_or("");
         if let Some(parent) = std::path::Path::new(path).parent() {
@@ -188,7 +207,7 @@ impl LooperTool for ListDirectoryTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let path = args["path"].as_str().unwrap_or(".");
         match tokio::fs::read_dir(path).await {
             Ok(mut entries) => {
@@ -236,7 +255,7 @@ impl LooperTool for GrepTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let pattern = args["pattern"].as_str().unwrap_or("");
         let path = args["path"].as_str().unwrap_or(".");
         let output = tokio::process::Command::new("grep")
@@ -282,7 +301,7 @@ impl LooperTool for FindFilesTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let pattern = args["pattern"].as_str().unwrap_or("*");
         let path = args["path"].as_str().unwrap_or(".");
         let output = tokio::process::Command::new("find")
@@ -303,26 +322,17 @@ impl LooperTool for FindFilesTool {
 // ── Tool sets ────────────────────────────────────────────────────────
 
 struct ToolSet {
-    tools: HashMap<String, Mutex<Arc<dyn LooperTool>>>,
+    tools: HashMap<String, Arc<dyn LooperTool>>,
 }
 
 impl ToolSet {
     fn new() -> Self {
-        let mut tools: HashMap<String, Mutex<Arc<dyn LooperTool>>> = HashMap::new();
-        tools.insert("read_file".to_string(), Mutex::new(Arc::new(ReadFileTool)));
-        tools.insert(
-            "write_file".to_string(),
-            Mutex::new(Arc::new(WriteFileTool)),
-        );
-        tools.insert(
-            "list_directory".to_string(),
-            Mutex::new(Arc::new(ListDirectoryTool)),
-        );
-        tools.insert("grep".to_string(), Mutex::new(Arc::new(GrepTool)));
-        tools.insert(
-            "find_files".to_string(),
-            Mutex::new(Arc::new(FindFilesTool)),
-        );
+        let mut tools: HashMap<String, Arc<dyn LooperTool>> = HashMap::new();
+        tools.insert("read_file".to_string(), Arc::new(ReadFileTool));
+        tools.insert("write_file".to_string(), Arc::new(WriteFileTool));
+        tools.insert("list_directory".to_string(), Arc::new(ListDirectoryTool));
+        tools.insert("grep".to_string(), Arc::new(GrepTool));
+        tools.insert("find_files".to_string(), Arc::new(FindFilesTool));
         ToolSet { tools }
     }
 }
@@ -333,8 +343,7 @@ impl LooperTools for ToolSet {
         let mut tools = Vec::with_capacity(self.tools.len());
 
         for t in self.tools.values() {
-            let guard = t.lock().await;
-            tools.push(guard.tool().clone());
+            tools.push(t.tool());
         }
 
         tools
@@ -342,16 +351,12 @@ impl LooperTools for ToolSet {
 
     async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
         let tool_name = tool.get_tool_name();
-        self.tools.insert(tool_name, Mutex::new(tool));
+        self.tools.insert(tool_name, tool);
     }
 
     async fn run_tool(&self, name: String, args: Value) -> Value {
         match self.tools.get(&name) {
-            Some(tool_mutex) => {
-                let mut arc = tool_mutex.lock().await;
-                let tool = Arc::get_mut(&mut arc).expect("tool has multiple references");
-                tool.execute(&args).await
-            }
+            Some(tool) => tool.execute(&args).await,
             None => json!({"error": format!("Unknown function: {}", name)}),
         }
     }
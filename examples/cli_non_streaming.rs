@@ -13,7 +13,6 @@ use looper::{
     tools::{LooperTool, LooperTools},
     types::{Handlers, LooperToolDefinition},
 };
-use tokio::sync::Mutex;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -90,7 +89,7 @@ impl LooperTool for ReadFileTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let path = args["path"].as_str().unwrap_or("");
         match tokio::fs::read_to_string(path).await {
             Ok(content) => json!({ "path": path, "content": content }),
@@ -120,7 +119,7 @@ impl LooperTool for ListDirectoryTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let path = args["path"].as_str().unwrap_or(".");
         match tokio::fs::read_dir(path).await {
             Ok(mut entries) => {
@@ -149,17 +148,14 @@ impl LooperTool for ListDirectoryTool {
 // ── Tool set ────────────────────────────────────────────────────────
 
 struct ToolSet {
-    tools: HashMap<String, Mutex<Arc<dyn LooperTool>>>,
+    tools: HashMap<String, Arc<dyn LooperTool>>,
 }
 
 impl ToolSet {
     fn new() -> Self {
-        let mut tools: HashMap<String, Mutex<Arc<dyn LooperTool>>> = HashMap::new();
-        tools.insert("read_file".to_string(), Mutex::new(Arc::new(ReadFileTool)));
-        tools.insert(
-            "list_directory".to_string(),
-            Mutex::new(Arc::new(ListDirectoryTool)),
-        );
+        let mut tools: HashMap<String, Arc<dyn LooperTool>> = HashMap::new();
+        tools.insert("read_file".to_string(), Arc::new(ReadFileTool));
+        tools.insert("list_directory".to_string(), Arc::new(ListDirectoryTool));
         ToolSet { tools }
     }
 }
@@ -170,8 +166,7 @@ impl LooperTools for ToolSet {
         let mut tools = Vec::with_capacity(self.tools.len());
 
         for t in self.tools.values() {
-            let guard = t.lock().await;
-            tools.push(guard.tool().clone());
+            tools.push(t.tool());
         }
 
         tools
@@ -179,16 +174,12 @@ impl LooperTools for ToolSet {
 
     async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
         let tool_name = tool.get_tool_name();
-        self.tools.insert(tool_name, Mutex::new(tool));
+        self.tools.insert(tool_name, tool);
     }
 
     async fn run_tool(&self, name: String, args: Value) -> Value {
         match self.tools.get(&name) {
-            Some(tool_mutex) => {
-                let mut arc = tool_mutex.lock().await;
-                let tool = Arc::get_mut(&mut arc).expect("tool has multiple references");
-                tool.execute(&args).await
-            }
+            Some(tool) => tool.execute(&args).await,
             None => json!({"error": format!("Unknown function: {}", name)}),
         }
     }
@@ -0,0 +1,56 @@
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use looper::{
+    looper::Looper,
+    tools::{LooperTools, McpToolSet, McpTransport},
+    types::Handlers,
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+
+    // Any MCP server works here, e.g. `McpTransport::http("http://localhost:8000/mcp")`
+    let transport = McpTransport::stdio(
+        "npx",
+        ["-y", "@modelcontextprotocol/server-filesystem", "."],
+    );
+
+    let tools: Box<dyn LooperTools> =
+        Box::new(McpToolSet::connect(transport).await?.with_tool_prefix("fs"));
+
+    for t in tools.get_tools().await {
+        println!("[mcp tool] {}", t.name);
+    }
+
+    let mut looper = Looper::builder(Handlers::Anthropic("claude-sonnet-4-6"))
+        .tools(tools)
+        .instructions("You're being used as an MCP client example for an agent loop. Be succinct yet friendly and helpful.")
+        .build()
+        .await?;
+
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        let result = looper.send(&input).await?;
+
+        for step in &result.steps {
+            for tc in &step.tool_calls {
+                println!("[tool: {}] args={}", tc.name, tc.args);
+            }
+        }
+
+        if let Some(final_text) = &result.final_text {
+            println!("{}", final_text);
+        }
+
+        println!("────────────────────────────────");
+    }
+}
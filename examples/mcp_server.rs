@@ -0,0 +1,30 @@
+use std::error::Error;
+
+use looper::{looper::Looper, looper_mcp_server::LooperMcpServer, types::Handlers};
+
+// Register with any MCP client as a stdio server, e.g.
+// `cargo run --example mcp_server --features mcp-server`
+//
+// Note that stdout is the MCP transport, so nothing else may print to it.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+
+    let looper = Looper::builder(Handlers::Anthropic("claude-sonnet-4-6"))
+        .instructions("
+            You are an agent researching specific tasks for another agent that is invoking you.
+            Report back with concise and clear findings since the agent invoking you will rely on this information.
+        ")
+        .build()
+        .await?;
+
+    LooperMcpServer::new(looper)
+        .agent_tool(
+            "ask_looper",
+            "Hands a task to a looper agent and returns its final answer.",
+        )
+        .serve_stdio()
+        .await?;
+
+    Ok(())
+}
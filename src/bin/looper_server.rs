@@ -0,0 +1,59 @@
+use std::error::Error;
+
+use looper::{looper_stream::LooperStream, server::LooperServer, types::Handlers};
+
+/// Configured through the environment:
+///
+/// - `LOOPER_PROVIDER`: `anthropic` (default), `openai_completions`, `openai_responses` or `gemini`
+/// - `LOOPER_MODEL`: model name, defaults to `claude-sonnet-4-6`
+/// - `LOOPER_INSTRUCTIONS`: optional instructions for every session
+/// - `LOOPER_ADDR`: bind address, defaults to `0.0.0.0:8080`
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+
+    #[cfg(feature = "otel")]
+    let _otel = looper::telemetry::init_otlp("looper-server")?;
+
+    let provider = std::env::var("LOOPER_PROVIDER").unwrap_or_else(|_| "anthropic".to_string());
+    let model = std::env::var("LOOPER_MODEL").unwrap_or_else(|_| "claude-sonnet-4-6".to_string());
+    let instructions = std::env::var("LOOPER_INSTRUCTIONS").ok();
+    let addr = std::env::var("LOOPER_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+
+    if !matches!(
+        provider.as_str(),
+        "anthropic" | "openai_completions" | "openai_responses" | "gemini"
+    ) {
+        return Err(format!("Unknown LOOPER_PROVIDER '{}'", provider).into());
+    }
+
+    let server = LooperServer::new(move |history| {
+        let provider = provider.clone();
+        let model = model.clone();
+        let instructions = instructions.clone();
+
+        async move {
+            let handler = match provider.as_str() {
+                "openai_completions" => Handlers::OpenAICompletions(&model),
+                "openai_responses" => Handlers::OpenAIResponses(&model),
+                "gemini" => Handlers::Gemini(&model),
+                _ => Handlers::Anthropic(&model),
+            };
+
+            let mut builder = LooperStream::builder(handler);
+            if let Some(h) = history {
+                builder = builder.message_history(h);
+            }
+            if let Some(i) = instructions {
+                builder = builder.instructions(i);
+            }
+
+            builder.build().await
+        }
+    });
+
+    eprintln!("looper server listening on {}", addr);
+    server.serve(addr).await?;
+
+    Ok(())
+}
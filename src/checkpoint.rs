@@ -0,0 +1,148 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    services::ProviderToolCall,
+    types::{
+        MessageHistory,
+        turn::{Citation, ServerToolRecord, StopReason, ToolCallRecord, TurnStep, Usage},
+    },
+};
+
+/// Everything needed to pick an interrupted turn back up.
+///
+/// Saved after every model response and every tool result, and cleared once
+/// the turn finishes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub turn_id: String,
+    /// The handoffs taken before it was saved, in order. Resuming takes
+    /// them again so the history goes back to the agent whose format it is.
+    #[serde(default)]
+    pub handoffs: Vec<String>,
+    /// Provider history as of the last model response, or from before the
+    /// user message for `TurnProgress::Started`.
+    pub message_history: MessageHistory,
+    pub steps: Vec<TurnStep>,
+    pub progress: TurnProgress,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TurnProgress {
+    /// The user message has not been answered yet.
+    Started { message: String },
+    /// The model asked for tools. Calls in `completed` are not run again.
+    ToolCallsPending(PendingToolCalls),
+    /// The provider paused its answer, the next request continues it.
+    Paused,
+    /// The model gave its final answer, only finishing the turn is left.
+    Complete,
+}
+
+/// A model response whose tool calls are (partly) still to be run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingToolCalls {
+    pub thinking: Vec<String>,
+    pub text: Option<String>,
+    pub calls: Vec<ProviderToolCall>,
+    pub completed: Vec<ToolCallRecord>,
+    #[serde(default)]
+    pub server_tool_calls: Vec<ServerToolRecord>,
+    #[serde(default)]
+    pub citations: Vec<Citation>,
+    #[serde(default)]
+    pub stop_reason: StopReason,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+/// Where checkpoints are persisted. `key` identifies the agent, at most one
+/// checkpoint per key is kept.
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    async fn save(&self, key: &str, checkpoint: &Checkpoint) -> Result<()>;
+    async fn load(&self, key: &str) -> Result<Option<Checkpoint>>;
+    async fn clear(&self, key: &str) -> Result<()>;
+}
+
+/// Keeps checkpoints in memory. Survives a failed turn but not a crash.
+#[derive(Default)]
+pub struct InMemoryCheckpointStore {
+    checkpoints: Mutex<HashMap<String, Checkpoint>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    async fn save(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        self.checkpoints
+            .lock()
+            .await
+            .insert(key.to_string(), checkpoint.clone());
+        Ok(())
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<Checkpoint>> {
+        Ok(self.checkpoints.lock().await.get(key).cloned())
+    }
+
+    async fn clear(&self, key: &str) -> Result<()> {
+        self.checkpoints.lock().await.remove(key);
+        Ok(())
+    }
+}
+
+/// Writes each checkpoint as `{dir}/{key}.json`.
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileCheckpointStore { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn save(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        // Write then rename so a crash mid-write never leaves a torn checkpoint
+        let path = self.path(key);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(checkpoint)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<Checkpoint>> {
+        match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn clear(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::types::{HandlerToLooperToolCallRequest, LooperToolDefinition, MessageHistory};
+
+/// The request about to be sent to the model, in a provider agnostic shape.
+///
+/// Changes only apply to this one request, the handler's conversation history
+/// is left untouched.
+pub struct HookRequest {
+    /// `None` for OpenAI Completions, where the system prompt is the first
+    /// entry of `messages` instead.
+    pub system_message: Option<String>,
+    /// The provider's native message list as JSON, the same format persisted
+    /// in `MessageHistory::Messages`. For OpenAI Responses this is the pending
+    /// input since the rest of the conversation is held server side.
+    pub messages: Value,
+    pub tools: Vec<LooperToolDefinition>,
+}
+
+impl HookRequest {
+    pub(crate) fn new<M: Serialize>(
+        system_message: Option<&str>,
+        messages: &M,
+        tools: &[LooperToolDefinition],
+    ) -> Result<Self> {
+        Ok(HookRequest {
+            system_message: system_message.map(|s| s.to_string()),
+            messages: serde_json::to_value(messages)?,
+            tools: tools.to_vec(),
+        })
+    }
+}
+
+/// What the model produced in a single response.
+pub struct HookResponse {
+    pub thinking: Vec<String>,
+    pub text: Option<String>,
+    pub tool_calls: Vec<HandlerToLooperToolCallRequest>,
+}
+
+pub enum ToolCallDecision {
+    Allow,
+    /// Skip the tool and hand `reason` back to the model as an error result.
+    Block(String),
+}
+
+/// Lifecycle callbacks around model and tool calls, invoked the same way by
+/// every handler. All methods default to doing nothing.
+#[async_trait]
+pub trait LooperHooks: Send + Sync {
+    /// Returning an error aborts the turn.
+    async fn before_request(&self, _request: &mut HookRequest) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_response(&self, _response: &HookResponse) {}
+
+    /// Called before argument validation, so rewritten args are validated too.
+    async fn before_tool_call(
+        &self,
+        _id: &str,
+        _name: &str,
+        _args: &mut Value,
+    ) -> ToolCallDecision {
+        ToolCallDecision::Allow
+    }
+
+    async fn after_tool_call(&self, _call: &HandlerToLooperToolCallRequest, _result: &mut Value) {}
+
+    async fn on_turn_complete(&self, _message_history: &MessageHistory) {}
+}
+
+/// Runs several hooks in registration order. A blocked tool call or failed
+/// request stops the chain.
+pub struct HookChain {
+    hooks: Vec<Arc<dyn LooperHooks>>,
+}
+
+impl HookChain {
+    pub fn new(hooks: Vec<Arc<dyn LooperHooks>>) -> Self {
+        HookChain { hooks }
+    }
+
+    /// `None` when no hooks are registered so handlers can skip the work of
+    /// building hook payloads entirely.
+    pub(crate) fn into_hooks(hooks: Vec<Arc<dyn LooperHooks>>) -> Option<Arc<dyn LooperHooks>> {
+        match hooks.len() {
+            0 => None,
+            1 => hooks.into_iter().next(),
+            _ => Some(Arc::new(HookChain::new(hooks))),
+        }
+    }
+}
+
+#[async_trait]
+impl LooperHooks for HookChain {
+    async fn before_request(&self, request: &mut HookRequest) -> Result<()> {
+        for h in &self.hooks {
+            h.before_request(request).await?;
+        }
+        Ok(())
+    }
+
+    async fn after_response(&self, response: &HookResponse) {
+        for h in &self.hooks {
+            h.after_response(response).await;
+        }
+    }
+
+    async fn before_tool_call(&self, id: &str, name: &str, args: &mut Value) -> ToolCallDecision {
+        for h in &self.hooks {
+            if let ToolCallDecision::Block(reason) = h.before_tool_call(id, name, args).await {
+                return ToolCallDecision::Block(reason);
+            }
+        }
+        ToolCallDecision::Allow
+    }
+
+    async fn after_tool_call(&self, call: &HandlerToLooperToolCallRequest, result: &mut Value) {
+        for h in &self.hooks {
+            h.after_tool_call(call, result).await;
+        }
+    }
+
+    async fn on_turn_complete(&self, message_history: &MessageHistory) {
+        for h in &self.hooks {
+            h.on_turn_complete(message_history).await;
+        }
+    }
+}
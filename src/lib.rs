@@ -1,6 +1,16 @@
+pub mod checkpoint;
+pub mod hooks;
 pub mod looper;
+#[cfg(feature = "mcp-server")]
+pub mod looper_mcp_server;
 pub mod looper_stream;
 pub mod mapping;
+pub mod pacing;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod services;
+pub mod subscription;
+pub mod system_prompt;
+pub mod telemetry;
 pub mod tools;
 pub mod types;
@@ -1,34 +1,29 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use tera::{Context, Tera};
+use serde_json::Value;
 
 use crate::{
-    services::{
-        ChatHandler,
-        handlers::{
-            anthropic_non_streaming::AnthropicNonStreamingHandler,
-            gemini_non_streaming::GeminiNonStreamingHandler,
-            openai_completions_non_streaming::OpenAINonStreamingChatHandler,
-            openai_responses_non_streaming::OpenAIResponsesNonStreamingHandler,
-        },
+    checkpoint::CheckpointStore,
+    hooks::LooperHooks,
+    services::{AgentConfig, AgentLoop, TurnState},
+    system_prompt::{PromptContext, PromptTemplate},
+    tools::{Handoff, LooperTool, LooperTools, RuntimeToolSet, SubAgentTool},
+    types::{
+        GenerationSettings, HandlerToLooperToolCallRequest, Handlers, MessageHistory, ServerTool,
+        turn::TurnResult,
     },
-    tools::{EmptyToolSet, LooperTools, SubAgentTool},
-    types::{Handlers, MessageHistory, turn::TurnResult},
 };
 
 pub struct Looper {
-    handler: Box<dyn ChatHandler>,
+    agent: AgentLoop,
     message_history: Option<MessageHistory>,
-    tools: Arc<dyn LooperTools>,
+    tools: Arc<RuntimeToolSet>,
 }
 
 pub struct LooperBuilder<'a> {
-    handler_type: Handlers<'a>,
+    config: AgentConfig<'a>,
     message_history: Option<MessageHistory>,
-    tools: Option<Box<dyn LooperTools>>,
-    instructions: Option<String>,
-    sub_agent: Option<Looper>,
 }
 
 impl<'a> LooperBuilder<'a> {
@@ -38,157 +33,224 @@ impl<'a> LooperBuilder<'a> {
     }
 
     pub fn tools(mut self, tools: Box<dyn LooperTools>) -> Self {
-        self.tools = Some(tools);
+        self.config.tools = Some(tools);
         self
     }
 
-    /// Sub Agent MUST receive a Looper instance with the *SAME* Tools
-    ///
-    /// This is currently a limitation that cannot be enforced a type level.
-    /// The main agent loop is expecting the Sub Agent to have the same tools
-    /// that it has!
+    /// Offers a tool the provider runs itself, like web search. `build`
+    /// fails if the provider does not have it. Call once per tool.
+    pub fn server_tool(mut self, tool: ServerTool) -> Self {
+        self.config.server_tools.push(tool);
+        self
+    }
+
+    /// Continues a response cut off at `max_tokens` with another request, at
+    /// most `max` times per turn. Off by default.
+    pub fn auto_continue(mut self, max: u32) -> Self {
+        self.config.auto_continue = max;
+        self
+    }
+
+    /// Attaches `looper` as the `spawn_sub_agent` tool.
     pub fn sub_agent(mut self, looper: Looper) -> Self {
-        self.sub_agent = Some(looper);
+        self.config.sub_agents.push(SubAgentTool::spawn(looper));
+        self
+    }
+
+    /// Attaches a named sub-agent. Call once per sub-agent.
+    pub fn sub_agent_tool(mut self, sub_agent: SubAgentTool) -> Self {
+        self.config.sub_agents.push(sub_agent);
+        self
+    }
+
+    /// Lets the model transfer the conversation to another agent with a
+    /// `transfer_to_{name}` tool. Call once per target.
+    pub fn handoff(mut self, handoff: Handoff) -> Self {
+        self.config.handoffs.push(handoff);
         self
     }
 
     pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
-        self.instructions = Some(instructions.into());
+        self.config.instructions = Some(instructions.into());
         self
     }
 
-    pub async fn build(mut self) -> Result<Looper> {
-        let sub_agent_enabled = self.sub_agent.is_some();
-
-        let handler: Box<dyn ChatHandler> = match self.handler_type {
-            Handlers::Anthropic(m) => {
-                let mut handler = AnthropicNonStreamingHandler::new(
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
-
-                Box::new(handler)
-            }
-            Handlers::OpenAICompletions(m) => {
-                let mut handler = OpenAINonStreamingChatHandler::new(
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
-
-                Box::new(handler)
-            }
-            Handlers::OpenAIResponses(m) => {
-                let mut handler = OpenAIResponsesNonStreamingHandler::new(
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
-
-                Box::new(handler)
-            }
-            Handlers::Gemini(m) => {
-                let mut handler = GeminiNonStreamingHandler::new(
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
-
-                Box::new(handler)
-            }
-        };
-
-        match self.tools {
-            Some(t) => Ok(Looper {
-                handler,
-                message_history: self.message_history,
-                tools: Arc::from(t),
-            }),
-            None => Ok(Looper {
-                handler,
-                message_history: self.message_history,
-                tools: Arc::new(EmptyToolSet),
-            }),
-        }
+    /// Registers lifecycle hooks. Hooks run in the order they were added.
+    pub fn hook(mut self, hook: Arc<dyn LooperHooks>) -> Self {
+        self.config.hooks.push(hook);
+        self
+    }
+
+    /// Checkpoints every turn under `key` so an interrupted turn can be
+    /// picked back up with `resume`.
+    pub fn checkpoint_store(
+        mut self,
+        store: Arc<dyn CheckpointStore>,
+        key: impl Into<String>,
+    ) -> Self {
+        self.config.checkpoints = Some((store, key.into()));
+        self
+    }
+
+    /// Max tokens, temperature and the like. Can be changed later with
+    /// `set_generation_settings`.
+    pub fn generation_settings(mut self, settings: GenerationSettings) -> Self {
+        self.config.generation_settings = settings;
+        self
+    }
+
+    /// Replaces the default system prompt template, see `SystemPrompt`.
+    pub fn system_prompt(mut self, template: PromptTemplate) -> Self {
+        self.config.system_prompt = template;
+        self
+    }
+
+    /// Sets a variable for the system prompt template.
+    pub fn prompt_var(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.config.prompt_vars.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds template values that are computed again at the start of every
+    /// turn.
+    pub fn prompt_context(mut self, context: Arc<dyn PromptContext>) -> Self {
+        self.config.prompt_contexts.push(context);
+        self
+    }
+
+    pub async fn build(self) -> Result<Looper> {
+        let (agent, tools) = AgentLoop::build(self.config, None).await?;
+
+        Ok(Looper {
+            agent,
+            message_history: self.message_history,
+            tools,
+        })
     }
 }
 
 impl Looper {
     pub fn builder(handler_type: Handlers) -> LooperBuilder {
         LooperBuilder {
-            handler_type,
+            config: AgentConfig::new(handler_type),
             message_history: None,
-            tools: None,
-            sub_agent: None,
-            instructions: None,
         }
     }
 
     pub async fn send(&mut self, message: &str) -> Result<TurnResult> {
         let result = self
-            .handler
+            .agent
             .send_message(self.message_history.clone(), message, self.tools.clone())
-            .await?;
+            .await;
+        self.adopt_handed_off_tools();
+        let result = result?;
 
         self.message_history = Some(result.message_history.clone());
 
         Ok(result)
     }
-}
 
-fn render_system_message(
-    template: &str,
-    instructions: Option<&str>,
-    sub_agent_enabled: bool,
-) -> Result<String> {
-    let mut tera = Tera::default();
-    tera.add_raw_template("system_prompt", template)?;
+    /// Finishes a turn interrupted by a crash or error from its last
+    /// checkpoint, without re-running tools that already completed. `None`
+    /// when there is no unfinished turn.
+    pub async fn resume(&mut self) -> Result<Option<TurnResult>> {
+        let result = self.agent.resume(self.tools.clone()).await;
+        self.adopt_handed_off_tools();
+        let result = result?;
+
+        if let Some(r) = &result {
+            self.message_history = Some(r.message_history.clone());
+        }
 
-    let mut ctx = Context::new();
-    if let Some(inst) = instructions {
-        ctx.insert("instructions", inst);
+        Ok(result)
     }
 
-    if sub_agent_enabled {
-        ctx.insert("sub_agent", &true);
+    /// Starts a turn to be driven one step at a time instead of running it
+    /// to completion with `send`.
+    pub async fn start_turn(&mut self, message: &str) -> Result<()> {
+        self.agent
+            .start_turn(self.message_history.clone(), message)
+            .await
     }
 
-    Ok(tera.render("system_prompt", &ctx)?)
-}
+    /// Restores the checkpointed turn to be driven with `step`. Returns
+    /// `false` when there is nothing to resume.
+    pub async fn resume_turn(&mut self) -> Result<bool> {
+        let resumed = self.agent.resume_turn().await;
+        self.adopt_handed_off_tools();
+        resumed
+    }
+
+    /// Sends the next model request, or runs the tool calls it asked for.
+    pub async fn step(&mut self) -> Result<TurnState> {
+        let state = self.agent.step(self.tools.clone()).await;
+        self.adopt_handed_off_tools();
+        state
+    }
+
+    pub fn pending_tool_calls(&self) -> impl Iterator<Item = &HandlerToLooperToolCallRequest> {
+        self.agent.pending_tool_calls()
+    }
+
+    pub async fn finish_turn(&mut self) -> Result<TurnResult> {
+        let result = self.agent.finish_turn().await?;
+        self.message_history = Some(result.message_history.clone());
+        Ok(result)
+    }
+
+    pub fn tools(&self) -> Arc<dyn LooperTools> {
+        self.tools.clone()
+    }
+
+    pub(crate) fn runtime_tools(&self) -> Arc<RuntimeToolSet> {
+        self.tools.clone()
+    }
+
+    pub(crate) fn into_parts(self) -> (AgentLoop, Arc<RuntimeToolSet>) {
+        (self.agent, self.tools)
+    }
 
-fn get_system_message(instructions: Option<&str>, sub_agent_enabled: bool) -> Result<String> {
-    render_system_message(
-        include_str!("../prompts/system_prompt.txt"),
-        instructions,
-        sub_agent_enabled,
-    )
+    /// After a handoff the target's tools are the ones to run and change.
+    fn adopt_handed_off_tools(&mut self) {
+        if let Some(tools) = self.agent.take_handed_off_tools() {
+            self.tools = tools;
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        self.agent.model()
+    }
+
+    /// Switches to another model of the same provider from the next
+    /// request on, keeping the conversation.
+    pub fn set_model(&mut self, model: &str) -> Result<()> {
+        self.agent.set_model(model)
+    }
+
+    pub fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.agent.set_generation_settings(settings);
+    }
+
+    /// Replaces the custom instructions, rendered into the system prompt
+    /// from the next turn on.
+    pub fn set_instructions(&mut self, instructions: impl Into<String>) {
+        self.agent
+            .set_prompt_var("instructions", instructions.into());
+    }
+
+    pub fn set_prompt_var(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.agent.set_prompt_var(key, value);
+    }
+
+    /// Adds a tool, or replaces the tool of the same name, from the next
+    /// request on.
+    pub async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
+        self.tools.insert(tool).await;
+        self.agent.set_tools(self.tools.get_tools().await);
+    }
+
+    pub async fn remove_tool(&mut self, name: &str) {
+        self.tools.remove(name).await;
+        self.agent.set_tools(self.tools.get_tools().await);
+    }
 }
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use rmcp::{
+    ErrorData, RoleServer, ServerHandler, ServiceExt,
+    model::{
+        CallToolRequestParams, CallToolResult, Content, Implementation, ListToolsResult,
+        PaginatedRequestParams, ServerCapabilities, ServerInfo, Tool,
+    },
+    service::RequestContext,
+    transport::stdio,
+};
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+
+use crate::{
+    looper::Looper,
+    tools::{LooperTools, RuntimeToolSet, ToolArgsValidator},
+};
+
+const DEFAULT_AGENT_TOOL_NAME: &str = "run_agent";
+const DEFAULT_AGENT_TOOL_DESCRIPTION: &str = "Hands a task to an autonomous agent that can use tools to complete it and returns the agent's final answer.";
+
+/// Serves a `Looper` as an MCP server so other agent frameworks can call it.
+///
+/// The agent is exposed as a single tool that accepts a `task` and returns
+/// `TurnResult::final_text`. Calls are serialized and share the looper's
+/// conversation history. Optionally, the looper's own tools are re-exported
+/// alongside the agent tool, without its sub-agent and handoff tools.
+pub struct LooperMcpServer {
+    looper: Arc<Mutex<Looper>>,
+    tools: Option<Arc<RuntimeToolSet>>,
+    agent_tool_name: String,
+    agent_tool_description: String,
+}
+
+impl LooperMcpServer {
+    pub fn new(looper: Looper) -> Self {
+        LooperMcpServer {
+            looper: Arc::new(Mutex::new(looper)),
+            tools: None,
+            agent_tool_name: DEFAULT_AGENT_TOOL_NAME.to_string(),
+            agent_tool_description: DEFAULT_AGENT_TOOL_DESCRIPTION.to_string(),
+        }
+    }
+
+    pub fn agent_tool(mut self, name: impl Into<String>, description: impl Into<String>) -> Self {
+        self.agent_tool_name = name.into();
+        self.agent_tool_description = description.into();
+        self
+    }
+
+    /// Re-export the looper's individual tools next to the agent tool.
+    /// Arguments are validated against each tool's schema before it runs.
+    pub async fn export_tools(mut self) -> Self {
+        self.tools = Some(self.looper.lock().await.runtime_tools());
+        self
+    }
+
+    /// Serves over stdin/stdout until the client disconnects.
+    pub async fn serve_stdio(self) -> Result<()> {
+        let service = self.serve(stdio()).await?;
+        service.waiting().await?;
+        Ok(())
+    }
+
+    fn agent_tool_definition(&self) -> Tool {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "task": { "type": "string", "description": "The task for the agent to perform." }
+            },
+            "required": ["task"]
+        });
+
+        let Value::Object(schema) = schema else {
+            unreachable!("schema is an object literal")
+        };
+
+        Tool::new(
+            self.agent_tool_name.clone(),
+            self.agent_tool_description.clone(),
+            schema,
+        )
+    }
+
+    async fn run_agent(&self, args: &Value) -> Result<CallToolResult, ErrorData> {
+        let Some(task) = args["task"].as_str() else {
+            return Err(ErrorData::invalid_params("Missing 'task' argument", None));
+        };
+
+        let result = self.looper.lock().await.send(task).await;
+
+        match result {
+            Ok(r) => match r.final_text {
+                Some(ft) => Ok(CallToolResult::success(vec![Content::text(ft)])),
+                None => Ok(CallToolResult::error(vec![Content::text(
+                    "The agent finished without producing a final answer",
+                )])),
+            },
+            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
+                "An error occured when running the agent | Error: {}",
+                e
+            ))])),
+        }
+    }
+}
+
+impl ServerHandler for LooperMcpServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: "looper".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Default::default()
+            },
+            instructions: Some(self.agent_tool_description.clone()),
+            ..Default::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        let mut tools = vec![self.agent_tool_definition()];
+
+        if let Some(t) = &self.tools {
+            for def in t.get_tools().await {
+                if t.is_delegate(&def.name).await {
+                    continue;
+                }
+                let Value::Object(schema) = def.parameters else {
+                    continue;
+                };
+                tools.push(Tool::new(def.name, def.description, schema));
+            }
+        }
+
+        Ok(ListToolsResult::with_all_items(tools))
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let args = Value::Object(request.arguments.unwrap_or_default());
+
+        if request.name == self.agent_tool_name {
+            return self.run_agent(&args).await;
+        }
+
+        let unknown = || ErrorData::invalid_params(format!("Unknown tool: {}", request.name), None);
+
+        let Some(t) = &self.tools else {
+            return Err(unknown());
+        };
+        // Sub-agents and handoffs only work inside the looper's own turns
+        if t.is_delegate(&request.name).await {
+            return Err(unknown());
+        }
+        let Some(def) = t
+            .get_tools()
+            .await
+            .into_iter()
+            .find(|def| def.name == request.name)
+        else {
+            return Err(unknown());
+        };
+
+        let validator = ToolArgsValidator::new(std::slice::from_ref(&def));
+        if let Err(e) = validator.validate("", &def.name, &args) {
+            return Ok(CallToolResult::error(vec![Content::text(
+                e.to_tool_result().to_string(),
+            )]));
+        }
+
+        let result = t.run_tool(request.name.to_string(), args).await;
+        let content = vec![Content::text(match &result {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })];
+
+        if result.get("error").is_some() {
+            Ok(CallToolResult::error(content))
+        } else {
+            Ok(CallToolResult::success(content))
+        }
+    }
+}
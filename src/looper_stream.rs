@@ -1,35 +1,61 @@
-use std::collections::VecDeque;
-use std::sync::Arc;
-use std::time::Duration;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use crate::{
+    checkpoint::CheckpointStore,
+    hooks::LooperHooks,
     looper::Looper,
-    services::{
-        StreamingChatHandler, anthropic::AnthropicHandler, gemini::GeminiHandler,
-        openai_completions::OpenAIChatHandler, openai_responses::OpenAIResponsesHandler,
+    pacing::{OutputPacing, Pacer},
+    services::{AgentConfig, AgentLoop, TurnState},
+    subscription::{SlowConsumer, Subscriber, Subscription},
+    system_prompt::{PromptContext, PromptTemplate},
+    tools::{Handoff, LooperTool, LooperTools, RuntimeToolSet, SubAgentTool},
+    types::{
+        GenerationSettings, HandlerToLooperMessage, HandlerToLooperToolCallRequest, Handlers,
+        InterfaceEvent, LooperToInterfaceMessage, MessageHistory, ServerTool, TurnStart,
+        turn::TurnResult,
+    },
+};
+use anyhow::{Result, anyhow};
+use futures::{Stream, future::BoxFuture};
+use serde_json::Value;
+use tokio::{
+    sync::{
+        mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender, error::SendError},
+        oneshot,
     },
-    tools::{EmptyToolSet, LooperTools, SubAgentTool},
-    types::{HandlerToLooperMessage, Handlers, LooperToInterfaceMessage, MessageHistory},
+    time::Instant,
 };
-use anyhow::Result;
-use tera::{Context, Tera};
-use tokio::sync::mpsc::{self, Receiver, Sender};
 
-const BUFFER_DRAIN_INTERVAL_MS: u64 = 5;
+const DEFAULT_EVENT_BUFFER: usize = 10000;
 
 pub struct LooperStream {
-    handler: Box<dyn StreamingChatHandler>,
+    agent: AgentLoop,
     message_history: Option<MessageHistory>,
-    tools: Arc<dyn LooperTools>,
+    tools: Arc<RuntimeToolSet>,
+    session_id: String,
+    routes: UnboundedSender<Route>,
+    subscriptions: UnboundedSender<Subscriber>,
+    event_buffer: usize,
+}
+
+/// An item of the stream returned by `LooperStream::send_stream`.
+#[derive(Debug)]
+pub enum TurnEvent {
+    Event(InterfaceEvent),
+    /// The last item of a turn that succeeded.
+    Complete(TurnResult),
 }
 
 pub struct LooperStreamBuilder<'a> {
-    handler_type: Handlers<'a>,
+    config: AgentConfig<'a>,
     message_history: Option<MessageHistory>,
-    tools: Option<Box<dyn LooperTools>>,
-    instructions: Option<String>,
-    sub_agent: Option<Looper>,
-    buffered_output: bool,
+    output_pacing: OutputPacing,
+    event_buffer: usize,
+    session_id: Option<String>,
 }
 
 impl<'a> LooperStreamBuilder<'a> {
@@ -39,269 +65,562 @@ impl<'a> LooperStreamBuilder<'a> {
     }
 
     pub fn tools(mut self, tools: Box<dyn LooperTools>) -> Self {
-        self.tools = Some(tools);
+        self.config.tools = Some(tools);
         self
     }
 
-    /// Sub Agent MUST receive a Looper instance with the *SAME* Tools
-    ///
-    /// This is currently a limitation that cannot be enforced a type level.
-    /// The main agent loop is expecting the Sub Agent to have the same tools
-    /// that it has!
+    /// Offers a tool the provider runs itself, like web search. `build`
+    /// fails if the provider does not have it. Call once per tool.
+    pub fn server_tool(mut self, tool: ServerTool) -> Self {
+        self.config.server_tools.push(tool);
+        self
+    }
+
+    /// Continues a response cut off at `max_tokens` with another request, at
+    /// most `max` times per turn. Off by default.
+    pub fn auto_continue(mut self, max: u32) -> Self {
+        self.config.auto_continue = max;
+        self
+    }
+
+    /// Attaches `looper` as the `spawn_sub_agent` tool.
     pub fn sub_agent(mut self, looper: Looper) -> Self {
-        self.sub_agent = Some(looper);
+        self.config.sub_agents.push(SubAgentTool::spawn(looper));
+        self
+    }
+
+    /// Attaches a named sub-agent. Call once per sub-agent.
+    pub fn sub_agent_tool(mut self, sub_agent: SubAgentTool) -> Self {
+        self.config.sub_agents.push(sub_agent);
+        self
+    }
+
+    /// Lets the model transfer the conversation to another agent with a
+    /// `transfer_to_{name}` tool. Call once per target.
+    pub fn handoff(mut self, handoff: Handoff) -> Self {
+        self.config.handoffs.push(handoff);
         self
     }
 
     pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
-        self.instructions = Some(instructions.into());
+        self.config.instructions = Some(instructions.into());
         self
     }
 
-    pub fn buffered_output(mut self) -> Self {
-        self.buffered_output = true;
+    /// Registers lifecycle hooks. Hooks run in the order they were added.
+    pub fn hook(mut self, hook: Arc<dyn LooperHooks>) -> Self {
+        self.config.hooks.push(hook);
         self
     }
 
-    pub async fn build(mut self) -> Result<(LooperStream, Receiver<LooperToInterfaceMessage>)> {
-        let sub_agent_enabled = self.sub_agent.is_some();
-        let (handler_looper_sender, mut handler_looper_receiver) = mpsc::channel(10000);
-        let (looper_ui_sender, looper_ui_receiver) = mpsc::channel(10000);
+    /// Checkpoints every turn under `key` so an interrupted turn can be
+    /// picked back up with `resume`.
+    pub fn checkpoint_store(
+        mut self,
+        store: Arc<dyn CheckpointStore>,
+        key: impl Into<String>,
+    ) -> Self {
+        self.config.checkpoints = Some((store, key.into()));
+        self
+    }
 
-        let handler: Box<dyn StreamingChatHandler> = match self.handler_type {
-            Handlers::OpenAICompletions(m) => {
-                let mut handler = OpenAIChatHandler::new(
-                    handler_looper_sender,
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
+    /// Max tokens, temperature and the like. Can be changed later with
+    /// `set_generation_settings`.
+    pub fn generation_settings(mut self, settings: GenerationSettings) -> Self {
+        self.config.generation_settings = settings;
+        self
+    }
 
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
+    /// Replaces the default system prompt template, see `SystemPrompt`.
+    pub fn system_prompt(mut self, template: PromptTemplate) -> Self {
+        self.config.system_prompt = template;
+        self
+    }
 
-                Box::new(handler)
-            }
-            Handlers::OpenAIResponses(m) => {
-                let mut handler = OpenAIResponsesHandler::new(
-                    handler_looper_sender,
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
+    /// Sets a variable for the system prompt template.
+    pub fn prompt_var(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.config.prompt_vars.push((key.into(), value.into()));
+        self
+    }
 
-                Box::new(handler)
-            }
-            Handlers::Anthropic(m) => {
-                let mut handler = AnthropicHandler::new(
-                    handler_looper_sender,
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
+    /// Adds template values that are computed again at the start of every
+    /// turn.
+    pub fn prompt_context(mut self, context: Arc<dyn PromptContext>) -> Self {
+        self.config.prompt_contexts.push(context);
+        self
+    }
 
-                Box::new(handler)
-            }
-            Handlers::Gemini(m) => {
-                let mut handler = GeminiHandler::new(
-                    handler_looper_sender,
-                    m,
-                    &get_system_message(self.instructions.as_deref(), sub_agent_enabled)?,
-                )?;
-
-                if let Some(t) = self.tools.as_mut() {
-                    if let Some(sa) = self.sub_agent {
-                        let agent_tools = Arc::new(SubAgentTool::new(sa));
-                        let _ = t.add_tool(agent_tools).await;
-                    }
-                    handler.set_tools(t.get_tools().await);
-                }
+    /// Smooths assistant text into steady character-by-character output,
+    /// the same as `output_pacing(OutputPacing::graphemes())`.
+    pub fn buffered_output(self) -> Self {
+        self.output_pacing(OutputPacing::graphemes())
+    }
 
-                Box::new(handler)
-            }
-        };
+    /// How assistant text is paced on its way to the interface. Deltas are
+    /// forwarded as they arrive by default.
+    pub fn output_pacing(mut self, pacing: OutputPacing) -> Self {
+        self.output_pacing = pacing;
+        self
+    }
+
+    /// How many events the receiver from `build`, the streams from
+    /// `send_stream` and the queues feeding them hold before the looper waits
+    /// for them to be read.
+    pub fn event_buffer(mut self, capacity: usize) -> Self {
+        self.event_buffer = capacity.max(1);
+        self
+    }
+
+    /// Id stamped on every `InterfaceEvent`. Defaults to a random UUID.
+    pub fn session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
 
-        // Spawn a single long-lived listener task that forwards messages
-        // from the handler to the interface and executes tool calls.
-        let buffered = self.buffered_output;
+    pub async fn build(self) -> Result<(LooperStream, Receiver<InterfaceEvent>)> {
+        let (handler_looper_sender, mut handler_looper_receiver) = mpsc::channel(self.event_buffer);
+        let (looper_ui_sender, looper_ui_receiver) = mpsc::channel(self.event_buffer);
+        let (sub_agent_sender, mut sub_agent_receiver) = mpsc::channel(self.event_buffer);
+        let (routes, mut route_receiver) = mpsc::unbounded_channel::<Route>();
+        let (subscriptions, subscription_receiver) = mpsc::unbounded_channel();
+
+        for sub_agent in &self.config.sub_agents {
+            sub_agent.forward_events_to(sub_agent_sender.clone());
+        }
+
+        let (agent, tools) = AgentLoop::build(self.config, Some(handler_looper_sender)).await?;
+
+        let session_id = self
+            .session_id
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // Spawn a single long-lived task that paces, stamps and routes the
+        // handler's and sub-agents' messages to the interface. Tool calls
+        // run in the agent loop, not here.
+        let mut forwarder = Forwarder {
+            channel: looper_ui_sender,
+            turn: None,
+            subscribers: Vec::new(),
+            subscriptions: subscription_receiver,
+            stamper: EventStamper::new(session_id.clone()),
+            pacer: Pacer::new(self.output_pacing),
+        };
         tokio::spawn(async move {
-            if buffered {
-                let mut pool: VecDeque<char> = VecDeque::new();
-                let mut interval =
-                    tokio::time::interval(Duration::from_millis(BUFFER_DRAIN_INTERVAL_MS));
-                let mut channel_open = true;
-                loop {
-                    tokio::select! {
-                        biased;
-                        msg = handler_looper_receiver.recv(), if channel_open => {
-                            match msg {
-                                Some(HandlerToLooperMessage::Assistant(m)) => {
-                                    pool.extend(m.chars());
-                                }
-                                Some(other) => {
-                                    if drain_pool(&looper_ui_sender, &mut pool).await.is_err() { break; }
-                                    if forward_non_text(&looper_ui_sender, other).await.is_err() { break; }
-                                }
-                                None => {
-                                    channel_open = false;
-                                }
-                            }
-                        }
-                        _ = interval.tick() => {
-                            if let Some(c) = pool.pop_front() {
-                                if looper_ui_sender.send(LooperToInterfaceMessage::Assistant(c.to_string())).await.is_err() { break; }
-                            } else if !channel_open {
-                                break;
-                            }
-                        }
+            loop {
+                let deadline = forwarder.pacer.deadline();
+                tokio::select! {
+                    // The clock goes first so a busy handler can't starve
+                    // paced text, and routes go last so they only change
+                    // once everything sent before them is forwarded
+                    biased;
+                    _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                        let text = forwarder.pacer.tick();
+                        forwarder.send_text(text).await;
                     }
-                }
-            } else {
-                while let Some(message) = handler_looper_receiver.recv().await {
-                    match message {
-                        HandlerToLooperMessage::Assistant(m) => {
-                            if looper_ui_sender
-                                .send(LooperToInterfaceMessage::Assistant(m))
-                                .await
-                                .is_err()
-                            {
-                                break;
-                            }
-                        }
-                        other => {
-                            if forward_non_text(&looper_ui_sender, other).await.is_err() {
-                                break;
-                            }
-                        }
+                    msg = handler_looper_receiver.recv() => match msg {
+                        Some(msg) => forwarder.forward(msg).await,
+                        None => break,
+                    },
+                    Some(event) = sub_agent_receiver.recv() => {
+                        forwarder.send(event).await;
+                    }
+                    Some(route) = route_receiver.recv() => {
+                        let text = forwarder.pacer.flush();
+                        forwarder.send_text(text).await;
+                        forwarder.turn = route.turn;
+                        let _ = route.applied.send(());
                     }
                 }
             }
+
+            let text = forwarder.pacer.flush();
+            forwarder.send_text(text).await;
         });
 
-        match self.tools {
-            Some(t) => {
-                let ls = LooperStream {
-                    handler,
-                    message_history: self.message_history,
-                    tools: Arc::from(t),
-                };
-                Ok((ls, looper_ui_receiver))
-            }
-            None => {
-                let ls = LooperStream {
-                    handler,
-                    message_history: self.message_history,
-                    tools: Arc::new(EmptyToolSet),
-                };
-                Ok((ls, looper_ui_receiver))
-            }
-        }
+        let ls = LooperStream {
+            agent,
+            message_history: self.message_history,
+            tools,
+            session_id,
+            routes,
+            subscriptions,
+            event_buffer: self.event_buffer,
+        };
+
+        Ok((ls, looper_ui_receiver))
     }
 }
 
 impl LooperStream {
     pub fn builder(handler_type: Handlers) -> LooperStreamBuilder {
         LooperStreamBuilder {
-            handler_type,
+            config: AgentConfig::new(handler_type),
             message_history: None,
-            tools: None,
-            sub_agent: None,
-            instructions: None,
             // interface_sender: None,
-            buffered_output: false,
+            output_pacing: OutputPacing::Immediate,
+            event_buffer: DEFAULT_EVENT_BUFFER,
+            session_id: None,
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Adds a consumer that sees every event from now on, whichever turn
+    /// or `send_stream` it belongs to. Each subscriber has its own buffer of
+    /// `buffer` events and `policy` decides what happens once it is full.
+    pub fn subscribe(&self, buffer: usize, policy: SlowConsumer) -> Subscription {
+        let (subscriber, subscription) = Subscriber::new(buffer, policy);
+        // Once the forwarding task is gone the subscription just ends
+        let _ = self.subscriptions.send(subscriber);
+        subscription
+    }
+
+    /// After a handoff the target's tools are the ones to run and change.
+    fn adopt_handed_off_tools(&mut self) {
+        if let Some(tools) = self.agent.take_handed_off_tools() {
+            self.tools = tools;
         }
     }
 
+    pub fn model(&self) -> &str {
+        self.agent.model()
+    }
+
+    /// Switches to another model of the same provider from the next
+    /// request on, keeping the conversation.
+    pub fn set_model(&mut self, model: &str) -> Result<()> {
+        self.agent.set_model(model)
+    }
+
+    pub fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.agent.set_generation_settings(settings);
+    }
+
+    /// Replaces the custom instructions, rendered into the system prompt
+    /// from the next turn on.
+    pub fn set_instructions(&mut self, instructions: impl Into<String>) {
+        self.agent
+            .set_prompt_var("instructions", instructions.into());
+    }
+
+    pub fn set_prompt_var(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.agent.set_prompt_var(key, value);
+    }
+
+    /// Adds a tool, or replaces the tool of the same name, from the next
+    /// request on.
+    pub async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
+        self.tools.insert(tool).await;
+        self.agent.set_tools(self.tools.get_tools().await);
+    }
+
+    pub async fn remove_tool(&mut self, name: &str) {
+        self.tools.remove(name).await;
+        self.agent.set_tools(self.tools.get_tools().await);
+    }
+
     pub async fn send(&mut self, message: &str) -> Result<MessageHistory> {
-        let history = self
-            .handler
+        Ok(self.run_turn(message).await?.message_history)
+    }
+
+    /// Runs a turn and streams its events, ending with its `TurnResult`, or
+    /// an error if the turn failed.
+    ///
+    /// Events of this turn go to the stream instead of the receiver from
+    /// `build`, which keeps getting the events of every other turn. Dropping
+    /// the stream cancels the turn.
+    pub fn send_stream<'s>(
+        &'s mut self,
+        message: &'s str,
+    ) -> impl Stream<Item = Result<TurnEvent>> + Send + 's {
+        let (sender, events) = mpsc::channel(self.event_buffer);
+
+        let turn = async move {
+            self.route(Some(sender)).await?;
+            let result = self.run_turn(message).await;
+            self.route(None).await.ok();
+            result
+        };
+
+        TurnStream {
+            turn: Some(Box::pin(turn)),
+            result: None,
+            events,
+        }
+    }
+
+    async fn run_turn(&mut self, message: &str) -> Result<TurnResult> {
+        let result = self
+            .agent
             .send_message(self.message_history.clone(), message, self.tools.clone())
-            .await?;
+            .await;
+        self.adopt_handed_off_tools();
+        let result = result?;
+
+        self.message_history = Some(result.message_history.clone());
+
+        Ok(result)
+    }
+
+    /// Points the forwarding task at a turn's stream, or back at the
+    /// channel, once it has forwarded everything already sent.
+    async fn route(&self, turn: Option<Sender<InterfaceEvent>>) -> Result<()> {
+        let (applied, done) = oneshot::channel();
+        self.routes
+            .send(Route { turn, applied })
+            .map_err(|_| anyhow!("The event forwarding task has stopped"))?;
+        done.await?;
+        Ok(())
+    }
+
+    /// Finishes a turn interrupted by a crash or error from its last
+    /// checkpoint, without re-running tools that already completed. `None`
+    /// when there is no unfinished turn.
+    pub async fn resume(&mut self) -> Result<Option<MessageHistory>> {
+        let result = self.agent.resume(self.tools.clone()).await;
+        self.adopt_handed_off_tools();
+        let Some(result) = result? else {
+            return Ok(None);
+        };
+
+        self.message_history = Some(result.message_history.clone());
+
+        Ok(Some(result.message_history))
+    }
+
+    /// Starts a turn to be driven one step at a time instead of running it
+    /// to completion with `send`.
+    pub async fn start_turn(&mut self, message: &str) -> Result<()> {
+        self.agent
+            .start_turn(self.message_history.clone(), message)
+            .await
+    }
+
+    /// Restores the checkpointed turn to be driven with `step`. Returns
+    /// `false` when there is nothing to resume.
+    pub async fn resume_turn(&mut self) -> Result<bool> {
+        let resumed = self.agent.resume_turn().await;
+        self.adopt_handed_off_tools();
+        resumed
+    }
+
+    /// Sends the next model request, or runs the tool calls it asked for.
+    pub async fn step(&mut self) -> Result<TurnState> {
+        let state = self.agent.step(self.tools.clone()).await;
+        self.adopt_handed_off_tools();
+        state
+    }
+
+    pub fn pending_tool_calls(&self) -> impl Iterator<Item = &HandlerToLooperToolCallRequest> {
+        self.agent.pending_tool_calls()
+    }
+
+    /// The id events of the current turn are stamped with, or of the last
+    /// turn once it is over.
+    pub fn turn_id(&self) -> &str {
+        self.agent.turn_id()
+    }
+
+    /// The zero based index of the step in progress, as `InterfaceEvent::step`
+    /// counts it.
+    pub fn step_index(&self) -> usize {
+        self.agent.step_index()
+    }
+
+    pub async fn finish_turn(&mut self) -> Result<TurnResult> {
+        let result = self.agent.finish_turn().await?;
+        self.message_history = Some(result.message_history.clone());
+        Ok(result)
+    }
+}
+
+/// Stamps interface messages with the session, turn and step they belong to.
+///
+/// Turn and step boundaries are derived from the message order itself so the
+/// stamps stay correct even when paced output lags behind the handler: a
+/// turn starts at `TurnStarted` with the id its checkpoints use, so a failed
+/// turn never shares an id with the next one, and a new step starts with the
+/// first message after a batch of `ToolCallComplete`s.
+struct EventStamper {
+    session_id: String,
+    turn_id: String,
+    step: usize,
+    tools_completed: bool,
+}
 
-        self.message_history = Some(history.clone());
+impl EventStamper {
+    fn new(session_id: String) -> Self {
+        EventStamper {
+            session_id,
+            turn_id: String::new(),
+            step: 0,
+            tools_completed: false,
+        }
+    }
+
+    fn start(&mut self, turn: TurnStart) {
+        self.turn_id = turn.turn_id;
+        self.step = turn.step;
+        self.tools_completed = false;
+    }
+
+    fn stamp(&mut self, message: LooperToInterfaceMessage) -> InterfaceEvent {
+        match message {
+            LooperToInterfaceMessage::ToolCallComplete(_) => self.tools_completed = true,
+            _ if self.tools_completed => {
+                self.tools_completed = false;
+                self.step += 1;
+            }
+            _ => {}
+        }
 
-        Ok(history)
+        InterfaceEvent::new(&self.session_id, &self.turn_id, self.step, message)
     }
 }
 
-async fn drain_pool(
-    sender: &Sender<LooperToInterfaceMessage>,
-    pool: &mut VecDeque<char>,
-) -> Result<()> {
-    let text: String = pool.drain(..).collect();
-    if !text.is_empty() {
-        sender
-            .send(LooperToInterfaceMessage::Assistant(text))
-            .await?;
-    }
-    Ok(())
+/// Where the forwarding task sends events: the channel returned by `build`
+/// when `turn` is `None`, or the stream of a `send_stream` turn.
+struct Route {
+    turn: Option<Sender<InterfaceEvent>>,
+    applied: oneshot::Sender<()>,
 }
 
-async fn forward_non_text(
-    sender: &Sender<LooperToInterfaceMessage>,
-    msg: HandlerToLooperMessage,
-) -> Result<()> {
-    let interface_msg = match msg {
-        HandlerToLooperMessage::Assistant(_) => unreachable!("Assistant handled separately"),
+struct Forwarder {
+    channel: Sender<InterfaceEvent>,
+    turn: Option<Sender<InterfaceEvent>>,
+    subscribers: Vec<Subscriber>,
+    subscriptions: UnboundedReceiver<Subscriber>,
+    stamper: EventStamper,
+    pacer: Pacer,
+}
+
+impl Forwarder {
+    async fn forward(&mut self, msg: HandlerToLooperMessage) {
+        match msg {
+            HandlerToLooperMessage::TurnStarted(turn) => {
+                // Text still held back belongs to the turn before
+                let text = self.pacer.flush();
+                self.send_text(text).await;
+                self.stamper.start(turn);
+            }
+            HandlerToLooperMessage::Assistant(m) => {
+                let text = self.pacer.push(&m);
+                self.send_text(text).await;
+            }
+            other => {
+                let text = self.pacer.flush();
+                self.send_text(text).await;
+                let event = self.stamper.stamp(interface_message(other));
+                self.send(event).await;
+            }
+        }
+    }
+
+    async fn send_text(&mut self, text: Option<String>) {
+        if let Some(text) = text {
+            let event = self
+                .stamper
+                .stamp(LooperToInterfaceMessage::Assistant(text));
+            self.send(event).await;
+        }
+    }
+
+    /// Events nobody is listening for are dropped, the channel receiver may
+    /// be gone when only `send_stream` is used.
+    async fn send(&mut self, event: InterfaceEvent) {
+        // Picked up here rather than in the select loop, so a subscriber
+        // sees everything sent after `subscribe` returned
+        while let Ok(subscriber) = self.subscriptions.try_recv() {
+            self.subscribers.push(subscriber);
+        }
+
+        let mut subscribers = Vec::with_capacity(self.subscribers.len());
+        for subscriber in self.subscribers.drain(..) {
+            if subscriber.send(event.clone()).await {
+                subscribers.push(subscriber);
+            }
+        }
+        self.subscribers = subscribers;
+
+        // A dropped stream cancelled its turn, whatever it left behind goes
+        // to the channel
+        let event = match &self.turn {
+            Some(turn) => match turn.send(event).await {
+                Ok(()) => return,
+                Err(SendError(event)) => {
+                    self.turn = None;
+                    event
+                }
+            },
+            None => event,
+        };
+        let _ = self.channel.send(event).await;
+    }
+}
+
+fn interface_message(msg: HandlerToLooperMessage) -> LooperToInterfaceMessage {
+    match msg {
+        HandlerToLooperMessage::TurnStarted(_) => unreachable!("turn starts are not forwarded"),
+        HandlerToLooperMessage::Assistant(m) => LooperToInterfaceMessage::Assistant(m),
         HandlerToLooperMessage::Thinking(m) => LooperToInterfaceMessage::Thinking(m),
         HandlerToLooperMessage::ThinkingComplete => LooperToInterfaceMessage::ThinkingComplete,
-        HandlerToLooperMessage::ToolCallPending(id) => {
-            LooperToInterfaceMessage::ToolCallPending(id)
+        HandlerToLooperMessage::ToolCallStarted(s) => LooperToInterfaceMessage::ToolCallStarted(s),
+        HandlerToLooperMessage::ToolCallArgsDelta(d) => {
+            LooperToInterfaceMessage::ToolCallArgsDelta(d)
+        }
+        HandlerToLooperMessage::ToolCallArgsComplete(tc) => {
+            LooperToInterfaceMessage::ToolCallArgsComplete(tc)
         }
-        HandlerToLooperMessage::ToolCallRequest(tc) => {
-            LooperToInterfaceMessage::ToolCall(tc.name.clone())
+        HandlerToLooperMessage::ToolCallExecuting(tc) => {
+            LooperToInterfaceMessage::ToolCallExecuting(tc)
         }
+        HandlerToLooperMessage::ToolCallInvalid(e) => LooperToInterfaceMessage::ToolCallInvalid(e),
         HandlerToLooperMessage::ToolCallComplete(id) => {
             LooperToInterfaceMessage::ToolCallComplete(id)
         }
+        HandlerToLooperMessage::ServerToolCall(c) => LooperToInterfaceMessage::ServerToolCall(c),
+        HandlerToLooperMessage::ServerToolResult(c) => {
+            LooperToInterfaceMessage::ServerToolResult(c)
+        }
+        HandlerToLooperMessage::Citation(c) => LooperToInterfaceMessage::Citation(c),
         HandlerToLooperMessage::TurnComplete => LooperToInterfaceMessage::TurnComplete,
-    };
-    sender.send(interface_msg).await?;
-    Ok(())
+    }
 }
 
-fn render_system_message(
-    template: &str,
-    instructions: Option<&str>,
-    sub_agent_enabled: bool,
-) -> Result<String> {
-    let mut tera = Tera::default();
-    tera.add_raw_template("system_prompt", template)?;
+/// Drives a `send_stream` turn while handing out its events. The result is
+/// held back until the forwarding task has let go of the stream, so it
+/// always comes after the last event.
+struct TurnStream<'s> {
+    turn: Option<BoxFuture<'s, Result<TurnResult>>>,
+    result: Option<Result<TurnResult>>,
+    events: Receiver<InterfaceEvent>,
+}
 
-    let mut ctx = Context::new();
-    if let Some(inst) = instructions {
-        ctx.insert("instructions", inst);
-    }
+impl Stream for TurnStream<'_> {
+    type Item = Result<TurnEvent>;
 
-    if sub_agent_enabled {
-        ctx.insert("sub_agent", &true);
-    }
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
 
-    Ok(tera.render("system_prompt", &ctx)?)
-}
+        if let Some(turn) = &mut this.turn
+            && let Poll::Ready(result) = turn.as_mut().poll(cx)
+        {
+            this.turn = None;
+            this.result = Some(result);
+        }
 
-fn get_system_message(instructions: Option<&str>, sub_agent_enabled: bool) -> Result<String> {
-    render_system_message(
-        include_str!("../prompts/system_prompt.txt"),
-        instructions,
-        sub_agent_enabled,
-    )
+        match this.events.poll_recv(cx) {
+            Poll::Ready(Some(event)) => return Poll::Ready(Some(Ok(TurnEvent::Event(event)))),
+            Poll::Ready(None) => {}
+            Poll::Pending => return Poll::Pending,
+        }
+
+        match this.result.take() {
+            Some(result) => Poll::Ready(Some(result.map(TurnEvent::Complete))),
+            None if this.turn.is_none() => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
 }
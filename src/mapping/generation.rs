@@ -0,0 +1,14 @@
+use async_openai::types::chat;
+
+use crate::types::ReasoningEffort;
+
+impl From<ReasoningEffort> for chat::ReasoningEffort {
+    fn from(value: ReasoningEffort) -> Self {
+        match value {
+            ReasoningEffort::Minimal => chat::ReasoningEffort::Minimal,
+            ReasoningEffort::Low => chat::ReasoningEffort::Low,
+            ReasoningEffort::Medium => chat::ReasoningEffort::Medium,
+            ReasoningEffort::High => chat::ReasoningEffort::High,
+        }
+    }
+}
@@ -1,4 +1,8 @@
+pub mod generation;
+
 pub mod tools;
 pub use tools::*;
 
 pub mod turn;
+
+pub(crate) mod transcript;
@@ -0,0 +1,13 @@
+use crate::types::LooperToolDefinition;
+use rmcp::model::Tool;
+use serde_json::Value;
+
+impl From<Tool> for LooperToolDefinition {
+    fn from(value: Tool) -> Self {
+        LooperToolDefinition {
+            name: value.name.to_string(),
+            description: value.description.map(|d| d.to_string()).unwrap_or_default(),
+            parameters: Value::Object((*value.input_schema).clone()),
+        }
+    }
+}
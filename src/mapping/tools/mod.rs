@@ -4,6 +4,9 @@ pub use anthropic::*;
 
 pub mod gemini;
 
+#[cfg(feature = "mcp")]
+pub mod mcp;
+
 pub mod openai_completions;
 #[allow(unused_imports)]
 pub use openai_completions::*;
@@ -0,0 +1,88 @@
+use serde_json::Value;
+
+/// Renders serialized history as plain lines, for an agent that can't load
+/// it in its own format.
+///
+/// Works on the message shapes of every provider: OpenAI Chat Completions
+/// messages, OpenAI Responses items, Anthropic content blocks and Gemini
+/// parts. Instructions, thinking and server tool blocks are left out.
+pub(crate) fn transcript(items: &[Value]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for item in items {
+        item_lines(item, &mut lines);
+    }
+    lines
+}
+
+fn item_lines(item: &Value, lines: &mut Vec<String>) {
+    // OpenAI Responses calls and their outputs are items of their own
+    match item["type"].as_str() {
+        Some("function_call") => return lines.push(call_line(item, "arguments")),
+        Some("function_call_output") => return lines.push(result_line(&item["output"])),
+        _ => {}
+    }
+
+    let role = match item["role"].as_str() {
+        Some("user") => "User",
+        Some("assistant" | "model") => "Assistant",
+        Some("tool") => return lines.push(result_line(&item["content"])),
+        _ => return,
+    };
+
+    // Gemini nests its parts one level deeper
+    let content = match &item["content"]["parts"] {
+        Value::Array(_) => &item["content"]["parts"],
+        _ => &item["content"],
+    };
+
+    match content {
+        Value::String(text) => lines.push(format!("{}: {}", role, text)),
+        Value::Array(blocks) => {
+            for block in blocks {
+                if block["thought"] == true {
+                    continue;
+                }
+
+                if let Some(text) = block["text"].as_str() {
+                    lines.push(format!("{}: {}", role, text));
+                } else if block["type"] == "tool_use" {
+                    lines.push(call_line(block, "input"));
+                } else if block["type"] == "tool_result" {
+                    lines.push(result_line(&block["content"]));
+                } else if block["functionCall"].is_object() {
+                    lines.push(call_line(&block["functionCall"], "args"));
+                } else if block["functionResponse"].is_object() {
+                    lines.push(result_line(&block["functionResponse"]["response"]));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // OpenAI Chat Completions keeps calls next to the content
+    if let Some(calls) = item["tool_calls"].as_array() {
+        for call in calls {
+            lines.push(call_line(&call["function"], "arguments"));
+        }
+    }
+}
+
+fn call_line(call: &Value, args: &str) -> String {
+    format!(
+        "Tool call: {}({})",
+        call["name"].as_str().unwrap_or_default(),
+        plain(&call[args])
+    )
+}
+
+fn result_line(result: &Value) -> String {
+    format!("Tool result: {}", plain(result))
+}
+
+/// Strings as they are, anything else as JSON.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
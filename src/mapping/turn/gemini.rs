@@ -1,33 +1,64 @@
-use crate::types::turn::{ThinkingBlock, TurnStep};
-use gemini_rust::GenerationResponse;
+use crate::types::turn::{Citation, StopReason};
+use gemini_rust::{FinishReason, GenerationResponse, GroundingMetadata};
 
-impl From<GenerationResponse> for TurnStep {
-    fn from(response: GenerationResponse) -> Self {
-        let mut thinking = Vec::new();
-        let mut text = None;
-        let tool_calls = Vec::new();
+/// `None` until the last chunk of a stream.
+pub(crate) fn stop_reason(response: &GenerationResponse) -> Option<StopReason> {
+    if let Some(feedback) = &response.prompt_feedback
+        && feedback.block_reason.is_some()
+    {
+        return Some(StopReason::ContentFilter);
+    }
 
-        for candidate in &response.candidates {
-            if let Some(parts) = &candidate.content.parts {
-                for part in parts {
-                    if let gemini_rust::Part::Text {
-                        text: t, thought, ..
-                    } = part
-                    {
-                        if *thought == Some(true) {
-                            thinking.push(ThinkingBlock { content: t.clone() });
-                        } else {
-                            text = Some(t.clone());
-                        }
-                    }
-                }
-            }
-        }
+    response
+        .candidates
+        .iter()
+        .find_map(|c| c.finish_reason.as_ref())
+        .map(Into::into)
+}
 
-        TurnStep {
-            thinking,
-            text,
-            tool_calls,
+/// Gemini reports calling a tool as a plain stop, the caller tells it apart
+/// by the function calls.
+impl From<&FinishReason> for StopReason {
+    fn from(reason: &FinishReason) -> Self {
+        match reason {
+            FinishReason::FinishReasonUnspecified | FinishReason::Stop => StopReason::EndTurn,
+            FinishReason::MaxTokens => StopReason::MaxTokens,
+            FinishReason::Safety
+            | FinishReason::Recitation
+            | FinishReason::Blocklist
+            | FinishReason::ProhibitedContent
+            | FinishReason::Spii
+            | FinishReason::ImageSafety => StopReason::ContentFilter,
+            _ => StopReason::Other,
         }
     }
 }
+
+/// One citation per web source backing each grounded segment. Spans are found
+/// by the segment text, the reported indices are relative to a single part.
+pub(crate) fn grounding_citations(metadata: &GroundingMetadata, text: &str) -> Vec<Citation> {
+    let chunks = metadata.grounding_chunks.as_deref().unwrap_or_default();
+
+    metadata
+        .grounding_supports
+        .iter()
+        .flatten()
+        .flat_map(|support| {
+            let segment = support.segment.text.clone();
+            let start = segment.as_deref().and_then(|s| text.find(s));
+            let end = start.zip(segment.as_deref()).map(|(i, s)| i + s.len());
+
+            support
+                .grounding_chunk_indices
+                .iter()
+                .filter_map(|&i| chunks.get(i as usize)?.web.as_ref())
+                .map(move |web| Citation {
+                    url: Some(web.uri.to_string()),
+                    title: Some(web.title.clone()),
+                    cited_text: segment.clone(),
+                    start,
+                    end,
+                })
+        })
+        .collect()
+}
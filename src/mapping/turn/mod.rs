@@ -1,3 +1,11 @@
 pub mod anthropic;
 pub mod gemini;
 pub mod openai_completions;
+
+/// Byte offset of the `index`th character of `text`. OpenAI reports citation
+/// spans in characters.
+pub(crate) fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(i, _)| i)
+}
@@ -1,16 +1,39 @@
-use crate::types::turn::TurnStep;
-use async_openai::types::chat::ChatChoice;
+use crate::{
+    mapping::turn::byte_offset,
+    types::turn::{Citation, StopReason},
+};
+use async_openai::types::chat::{
+    ChatCompletionResponseMessage, ChatCompletionResponseMessageAnnotation, FinishReason,
+};
 
-impl From<ChatChoice> for TurnStep {
-    fn from(choice: ChatChoice) -> Self {
-        let text = choice.message.content;
-
-        TurnStep {
-            thinking: Vec::new(),
-            text,
-            // Tool calls are handled separately in the handler
-            // since they need to be executed and recorded with results
-            tool_calls: Vec::new(),
+impl From<FinishReason> for StopReason {
+    fn from(reason: FinishReason) -> Self {
+        match reason {
+            FinishReason::Stop => StopReason::EndTurn,
+            FinishReason::Length => StopReason::MaxTokens,
+            FinishReason::ToolCalls | FinishReason::FunctionCall => StopReason::ToolUse,
+            FinishReason::ContentFilter => StopReason::ContentFilter,
         }
     }
 }
+
+/// URL citations the search models attach to a message.
+pub(crate) fn citations(message: &ChatCompletionResponseMessage) -> Vec<Citation> {
+    let text = message.content.as_deref().unwrap_or_default();
+
+    message
+        .annotations
+        .iter()
+        .flatten()
+        .map(|annotation| {
+            let ChatCompletionResponseMessageAnnotation::UrlCitation { url_citation } = annotation;
+            Citation {
+                url: Some(url_citation.url.clone()),
+                title: Some(url_citation.title.clone()),
+                cited_text: None,
+                start: Some(byte_offset(text, url_citation.start_index as usize)),
+                end: Some(byte_offset(text, url_citation.end_index as usize)),
+            }
+        })
+        .collect()
+}
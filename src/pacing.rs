@@ -0,0 +1,315 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How `LooperStream` hands assistant text to the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputPacing {
+    /// Forwards every delta as the provider sends it.
+    #[default]
+    Immediate,
+    /// Releases text a unit at a time every `interval`. When a backlog
+    /// builds up more units go out per tick, so the text never trails the
+    /// model by much more than `max_lag`.
+    Adaptive {
+        unit: TextUnit,
+        interval: Duration,
+        max_lag: Duration,
+    },
+    /// Holds text back until a sentence or paragraph ends.
+    Sentences,
+    /// Holds text back until a line ends.
+    Lines,
+    /// Joins the deltas that arrive within the window into one event.
+    Coalesce(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextUnit {
+    /// A user-perceived character, so emoji and accents are never split.
+    Grapheme,
+    /// A word with the whitespace that follows it.
+    Word,
+}
+
+impl OutputPacing {
+    /// Smooth character rendering, what `buffered_output` turns on.
+    pub fn graphemes() -> Self {
+        OutputPacing::Adaptive {
+            unit: TextUnit::Grapheme,
+            interval: Duration::from_millis(5),
+            max_lag: Duration::from_millis(250),
+        }
+    }
+
+    pub fn words() -> Self {
+        OutputPacing::Adaptive {
+            unit: TextUnit::Word,
+            interval: Duration::from_millis(30),
+            max_lag: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Holds assistant text between the handler and the interface according to
+/// an `OutputPacing`.
+///
+/// Text only ever leaves in the order it came in. The caller flushes before
+/// forwarding any other event so text stays ahead of the tool calls and
+/// turn boundaries that followed it.
+pub(crate) struct Pacer {
+    pacing: OutputPacing,
+    pending: String,
+    deadline: Option<Instant>,
+    last_release: Option<Instant>,
+}
+
+impl Pacer {
+    pub(crate) fn new(pacing: OutputPacing) -> Self {
+        Pacer {
+            pacing,
+            pending: String::new(),
+            deadline: None,
+            last_release: None,
+        }
+    }
+
+    /// Takes a delta, returning whatever can go out right away.
+    pub(crate) fn push(&mut self, text: &str) -> Option<String> {
+        self.pending.push_str(text);
+
+        match self.pacing {
+            OutputPacing::Immediate => self.flush(),
+            OutputPacing::Sentences => self.release_to(last_boundary(&self.pending, sentence_end)),
+            OutputPacing::Lines => self.release_to(last_boundary(&self.pending, |_, c| c == '\n')),
+            OutputPacing::Coalesce(window) => {
+                self.deadline.get_or_insert_with(|| Instant::now() + window);
+                None
+            }
+            OutputPacing::Adaptive { interval, .. } => {
+                if self.deadline.is_none() {
+                    let now = Instant::now();
+                    let next = self.last_release.map_or(now, |last| last + interval);
+                    self.deadline = Some(next.max(now));
+                }
+                None
+            }
+        }
+    }
+
+    /// When `tick` should next be called, if there is anything waiting on
+    /// the clock.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub(crate) fn tick(&mut self) -> Option<String> {
+        self.deadline = None;
+
+        let OutputPacing::Adaptive {
+            unit,
+            interval,
+            max_lag,
+        } = self.pacing
+        else {
+            return self.flush();
+        };
+
+        // The last unit may still grow with the next delta, so it stays
+        // until a flush
+        let ends = unit_ends(&self.pending, unit);
+        if ends.is_empty() {
+            return None;
+        }
+
+        let ticks = (max_lag.as_secs_f64() / interval.as_secs_f64()).max(1.0);
+        let count = ((ends.len() as f64 / ticks).ceil() as usize).clamp(1, ends.len());
+        let released = self.release_to(Some(ends[count - 1]));
+
+        let now = Instant::now();
+        self.last_release = Some(now);
+        if count < ends.len() {
+            self.deadline = Some(now + interval);
+        }
+        released
+    }
+
+    /// Lets everything held back go.
+    pub(crate) fn flush(&mut self) -> Option<String> {
+        self.deadline = None;
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    fn release_to(&mut self, end: Option<usize>) -> Option<String> {
+        let end = end.filter(|&end| end > 0)?;
+        let rest = self.pending.split_off(end);
+        Some(std::mem::replace(&mut self.pending, rest))
+    }
+}
+
+/// Byte offsets where each complete unit of `text` ends, leaving out the
+/// last one which runs to the end of the text.
+fn unit_ends(text: &str, unit: TextUnit) -> Vec<usize> {
+    match unit {
+        TextUnit::Grapheme => text
+            .grapheme_indices(true)
+            .skip(1)
+            .map(|(i, _)| i)
+            .collect(),
+        TextUnit::Word => {
+            let mut prev_whitespace = false;
+            text.char_indices()
+                .filter_map(|(i, c)| {
+                    let starts_word = prev_whitespace && !c.is_whitespace();
+                    prev_whitespace = c.is_whitespace();
+                    starts_word.then_some(i)
+                })
+                .collect()
+        }
+    }
+}
+
+/// The offset just past the last character `is_end` accepts, given that
+/// character and the one before it.
+fn last_boundary(text: &str, is_end: impl Fn(Option<char>, char) -> bool) -> Option<usize> {
+    let mut prev = None;
+    let mut boundary = None;
+    for (i, c) in text.char_indices() {
+        if is_end(prev, c) {
+            boundary = Some(i + c.len_utf8());
+        }
+        prev = Some(c);
+    }
+    boundary
+}
+
+/// Whitespace after terminal punctuation, or any line break. Waiting for
+/// the whitespace keeps a number like "3.5" in one piece mid-stream.
+fn sentence_end(prev: Option<char>, c: char) -> bool {
+    c == '\n' || (c.is_whitespace() && matches!(prev, Some('.' | '!' | '?' | '…')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive(unit: TextUnit) -> Pacer {
+        // 50 ticks to catch up with a backlog
+        Pacer::new(OutputPacing::Adaptive {
+            unit,
+            interval: Duration::from_millis(5),
+            max_lag: Duration::from_millis(250),
+        })
+    }
+
+    #[test]
+    fn immediate_forwards_every_delta() {
+        let mut pacer = Pacer::new(OutputPacing::Immediate);
+
+        assert_eq!(pacer.push("Hel").as_deref(), Some("Hel"));
+        assert_eq!(pacer.push("lo").as_deref(), Some("lo"));
+        assert_eq!(pacer.deadline(), None);
+        assert_eq!(pacer.flush(), None);
+    }
+
+    #[test]
+    fn sentences_wait_for_the_whitespace_after_punctuation() {
+        let mut pacer = Pacer::new(OutputPacing::Sentences);
+
+        assert_eq!(pacer.push("Pi is 3.1"), None);
+        assert_eq!(pacer.push("4. Next"), Some("Pi is 3.14. ".to_string()));
+        assert_eq!(pacer.push(" one\nand"), Some("Next one\n".to_string()));
+        assert_eq!(pacer.deadline(), None);
+        assert_eq!(pacer.flush().as_deref(), Some("and"));
+    }
+
+    #[test]
+    fn lines_wait_for_a_line_break() {
+        let mut pacer = Pacer::new(OutputPacing::Lines);
+
+        assert_eq!(pacer.push("fn main() {"), None);
+        assert_eq!(pacer.push("\n    let a"), Some("fn main() {\n".to_string()));
+        assert_eq!(pacer.flush().as_deref(), Some("    let a"));
+    }
+
+    #[test]
+    fn coalesce_joins_deltas_until_the_window_ends() {
+        let mut pacer = Pacer::new(OutputPacing::Coalesce(Duration::from_millis(50)));
+
+        assert_eq!(pacer.push("a"), None);
+        let deadline = pacer.deadline().unwrap();
+        assert_eq!(pacer.push("b"), None);
+        // Later deltas don't push the window back
+        assert_eq!(pacer.deadline(), Some(deadline));
+
+        assert_eq!(pacer.tick().as_deref(), Some("ab"));
+        assert_eq!(pacer.deadline(), None);
+        assert_eq!(pacer.tick(), None);
+    }
+
+    #[test]
+    fn adaptive_graphemes_release_one_at_a_time_and_hold_the_last() {
+        let mut pacer = adaptive(TextUnit::Grapheme);
+
+        assert_eq!(pacer.push("ne\u{301}e"), None);
+        assert!(pacer.deadline().is_some());
+
+        assert_eq!(pacer.tick().as_deref(), Some("n"));
+        // An accent is never split from its letter
+        assert_eq!(pacer.tick().as_deref(), Some("e\u{301}"));
+        // The last one could still grow with the next delta
+        assert_eq!(pacer.tick(), None);
+        assert_eq!(pacer.deadline(), None);
+
+        assert_eq!(pacer.push("👍🏽"), None);
+        assert!(pacer.deadline().is_some());
+        assert_eq!(pacer.tick().as_deref(), Some("e"));
+        assert_eq!(pacer.flush().as_deref(), Some("👍🏽"));
+    }
+
+    #[test]
+    fn adaptive_catches_up_with_a_backlog() {
+        let mut pacer = adaptive(TextUnit::Grapheme);
+
+        pacer.push(&"a".repeat(1001));
+
+        // 1000 complete units spread over 50 ticks
+        assert_eq!(pacer.tick().map(|t| t.len()), Some(20));
+        assert!(pacer.deadline().is_some());
+    }
+
+    #[test]
+    fn adaptive_words_keep_their_trailing_whitespace() {
+        let mut pacer = adaptive(TextUnit::Word);
+
+        pacer.push("Hello wor");
+        assert_eq!(pacer.tick().as_deref(), Some("Hello "));
+        assert_eq!(pacer.tick(), None);
+
+        pacer.push("ld  again");
+        assert_eq!(pacer.tick().as_deref(), Some("world  "));
+        assert_eq!(pacer.flush().as_deref(), Some("again"));
+    }
+
+    #[test]
+    fn flush_lets_everything_go_once() {
+        for pacing in [
+            OutputPacing::Sentences,
+            OutputPacing::Lines,
+            OutputPacing::Coalesce(Duration::from_millis(50)),
+            OutputPacing::graphemes(),
+            OutputPacing::words(),
+        ] {
+            let mut pacer = Pacer::new(pacing);
+
+            assert_eq!(pacer.push("held back"), None, "{:?}", pacing);
+            assert_eq!(pacer.flush().as_deref(), Some("held back"), "{:?}", pacing);
+            assert_eq!(pacer.deadline(), None, "{:?}", pacing);
+            assert_eq!(pacer.flush(), None, "{:?}", pacing);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{INTERFACE_EVENT_SCHEMA_VERSION, InterfaceEvent, now_ms};
+
+/// JSON event pushed to session subscribers over SSE.
+///
+/// Looper events are sent as-is in the `InterfaceEvent` wire format. The
+/// server adds its own `error` event for turns that fail, in the same
+/// envelope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// The turn failed. No `turn_complete` follows.
+    Error {
+        schema_version: u32,
+        session_id: String,
+        turn_id: String,
+        step: usize,
+        /// Milliseconds since the Unix epoch
+        timestamp_ms: u64,
+        data: ErrorData,
+    },
+    #[serde(untagged)]
+    Interface(InterfaceEvent),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorData {
+    pub message: String,
+}
+
+impl ServerEvent {
+    /// An `error` event for the turn and step that failed.
+    pub fn error(
+        session_id: impl Into<String>,
+        turn_id: impl Into<String>,
+        step: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        ServerEvent::Error {
+            schema_version: INTERFACE_EVENT_SCHEMA_VERSION,
+            session_id: session_id.into(),
+            turn_id: turn_id.into(),
+            step,
+            timestamp_ms: now_ms(),
+            data: ErrorData {
+                message: message.into(),
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Error { .. } => "error",
+            ServerEvent::Interface(e) => e.message.event_type(),
+        }
+    }
+}
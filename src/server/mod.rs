@@ -0,0 +1,307 @@
+pub mod events;
+pub use events::*;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    pin::pin,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use futures::{Stream, StreamExt, future::BoxFuture};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    net::{TcpListener, ToSocketAddrs},
+    sync::{Mutex, RwLock, broadcast, mpsc::Receiver},
+    task::AbortHandle,
+};
+use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
+
+use crate::{
+    looper_stream::{LooperStream, TurnEvent},
+    types::{InterfaceEvent, MessageHistory},
+};
+
+const SESSION_EVENT_BUFFER: usize = 1024;
+
+type SessionFactory = Arc<
+    dyn Fn(
+            Option<MessageHistory>,
+        ) -> BoxFuture<'static, Result<(LooperStream, Receiver<InterfaceEvent>)>>
+        + Send
+        + Sync,
+>;
+
+struct Session {
+    looper: Arc<Mutex<LooperStream>>,
+    events: broadcast::Sender<ServerEvent>,
+    message_history: RwLock<Option<MessageHistory>>,
+    /// The running turn, aborted when the session is deleted.
+    turn: StdMutex<Option<AbortHandle>>,
+}
+
+/// Hosts many concurrent `LooperStream` sessions behind a REST + SSE API.
+///
+/// | Route | Description |
+/// |---|---|
+/// | `POST /sessions` | Create a session, optionally resuming from `{"message_history": ...}` |
+/// | `GET /sessions` | List session ids |
+/// | `GET /sessions/{id}` | Session status and current `message_history` |
+/// | `DELETE /sessions/{id}` | Delete a session |
+/// | `POST /sessions/{id}/messages` | Start a turn with `{"message": "..."}` |
+/// | `GET /sessions/{id}/events` | SSE stream of `ServerEvent`s |
+///
+/// Events are not buffered for clients that aren't subscribed yet, so
+/// subscribe to a session's events before starting a turn. The finished
+/// history can always be read back from `GET /sessions/{id}`.
+pub struct LooperServer {
+    factory: SessionFactory,
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+}
+
+impl LooperServer {
+    /// `factory` builds a fresh `LooperStream` for every new session, using
+    /// the given history when a client resumes a previous conversation.
+    pub fn new<F, Fut>(factory: F) -> Self
+    where
+        F: Fn(Option<MessageHistory>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(LooperStream, Receiver<InterfaceEvent>)>> + Send + 'static,
+    {
+        LooperServer {
+            factory: Arc::new(move |history| Box::pin(factory(history))),
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/sessions", post(create_session).get(list_sessions))
+            .route("/sessions/{id}", get(get_session).delete(delete_session))
+            .route("/sessions/{id}/messages", post(send_message))
+            .route("/sessions/{id}/events", get(session_events))
+            .with_state(Arc::new(self))
+    }
+
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    pub async fn create_session(&self, message_history: Option<MessageHistory>) -> Result<String> {
+        let (looper, mut rx) = (self.factory)(message_history.clone()).await?;
+        let (events, _) = broadcast::channel(SESSION_EVENT_BUFFER);
+
+        // Fan the looper's single receiver out to every SSE subscriber.
+        // Ends once the session (and with it the looper) is dropped.
+        let forward = events.clone();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let _ = forward.send(ServerEvent::Interface(event));
+            }
+        });
+
+        let id = looper.session_id().to_string();
+        let session = Session {
+            looper: Arc::new(Mutex::new(looper)),
+            events,
+            message_history: RwLock::new(message_history),
+            turn: StdMutex::new(None),
+        };
+
+        self.sessions
+            .write()
+            .await
+            .insert(id.clone(), Arc::new(session));
+
+        Ok(id)
+    }
+
+    /// Deletes a session, cancelling its turn if one is running.
+    pub async fn delete_session(&self, id: &str) -> bool {
+        let Some(session) = self.sessions.write().await.remove(id) else {
+            return false;
+        };
+
+        if let Some(turn) = session.turn.lock().unwrap().take() {
+            turn.abort();
+        }
+        true
+    }
+
+    async fn session(&self, id: &str) -> Result<Arc<Session>, ApiError> {
+        self.sessions
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or(ApiError::NotFound)
+    }
+}
+
+enum ApiError {
+    NotFound,
+    Busy,
+    Internal(anyhow::Error),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "Session not found".to_string()),
+            ApiError::Busy => (
+                StatusCode::CONFLICT,
+                "A turn is already in progress for this session".to_string(),
+            ),
+            ApiError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        };
+
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct CreateSessionRequest {
+    message_history: Option<MessageHistory>,
+}
+
+#[derive(Deserialize)]
+struct SendMessageRequest {
+    message: String,
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    id: String,
+    busy: bool,
+    message_history: Option<MessageHistory>,
+}
+
+async fn create_session(
+    State(server): State<Arc<LooperServer>>,
+    body: Option<Json<CreateSessionRequest>>,
+) -> Result<(StatusCode, Json<SessionResponse>), ApiError> {
+    let Json(body) = body.unwrap_or_default();
+    let id = server
+        .create_session(body.message_history.clone())
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SessionResponse {
+            id,
+            busy: false,
+            message_history: body.message_history,
+        }),
+    ))
+}
+
+async fn list_sessions(State(server): State<Arc<LooperServer>>) -> Json<Vec<String>> {
+    Json(server.sessions.read().await.keys().cloned().collect())
+}
+
+async fn get_session(
+    State(server): State<Arc<LooperServer>>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionResponse>, ApiError> {
+    let session = server.session(&id).await?;
+
+    Ok(Json(SessionResponse {
+        id,
+        busy: session.looper.try_lock().is_err(),
+        message_history: session.message_history.read().await.clone(),
+    }))
+}
+
+async fn delete_session(
+    State(server): State<Arc<LooperServer>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if server.delete_session(&id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+async fn send_message(
+    State(server): State<Arc<LooperServer>>,
+    Path(id): Path<String>,
+    Json(body): Json<SendMessageRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = server.session(&id).await?;
+    let Ok(mut looper) = session.looper.clone().try_lock_owned() else {
+        return Err(ApiError::Busy);
+    };
+
+    // Turns can run for minutes, so the request only kicks the turn off.
+    // Progress and completion are observed on the session's event stream.
+    let turn = tokio::spawn({
+        let session = session.clone();
+        async move {
+            // The turn's stream yields its result after all of its events, so
+            // an error never overtakes the events that led up to it
+            let mut failure = None;
+            {
+                let mut events = pin!(looper.send_stream(&body.message));
+                while let Some(item) = events.next().await {
+                    match item {
+                        Ok(TurnEvent::Event(event)) => {
+                            let _ = session.events.send(ServerEvent::Interface(event));
+                        }
+                        Ok(TurnEvent::Complete(result)) => {
+                            *session.message_history.write().await = Some(result.message_history);
+                        }
+                        Err(e) => failure = Some(e),
+                    }
+                }
+            }
+
+            if let Some(e) = failure {
+                let _ = session.events.send(ServerEvent::error(
+                    id,
+                    looper.turn_id(),
+                    looper.step_index(),
+                    e.to_string(),
+                ));
+            }
+        }
+    });
+    *session.turn.lock().unwrap() = Some(turn.abort_handle());
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn session_events(
+    State(server): State<Arc<LooperServer>>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let session = server.session(&id).await?;
+
+    let stream = BroadcastStream::new(session.events.subscribe()).filter_map(|event| async move {
+        let event = match event {
+            Ok(e) => Event::default().event(e.name()).json_data(&e).ok()?,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => Event::default()
+                .event("lagged")
+                .data(json!({ "type": "lagged", "skipped": skipped }).to_string()),
+        };
+
+        Some(Ok(event))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,823 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use tokio::{sync::mpsc::Sender, task::JoinSet};
+use tracing::{Instrument, Span, error};
+
+use crate::{
+    checkpoint::{Checkpoint, CheckpointStore, PendingToolCalls, TurnProgress},
+    hooks::{HookChain, HookResponse, LooperHooks},
+    services::{Provider, ProviderToolCall, create_provider},
+    system_prompt::{PromptContext, PromptTemplate, SystemPrompt},
+    telemetry::{ChatSpan, agent_span},
+    tools::{
+        EmptyToolSet, Handoff, LooperTools, RuntimeToolSet, SubAgentTool, ToolArgsValidator,
+        ToolExecutor,
+    },
+    types::{
+        GenerationSettings, HandlerToLooperMessage, HandlerToLooperToolCallRequest, Handlers,
+        LooperToolDefinition, MessageHistory, ServerTool, TurnStart,
+        turn::{StopReason, ThinkingBlock, ToolCallRecord, TurnResult, TurnStep, Usage},
+    },
+};
+
+const CONTINUE_PROMPT: &str = "Your last response was cut off at the output token limit. Continue exactly where it stopped, without repeating anything.";
+
+const TRUNCATED_ARGS: &str = "The response hit the output token limit before these arguments were complete. Call the tool again with shorter arguments, for example by splitting large content across several calls.";
+
+/// The builder settings `LooperBuilder` and `LooperStreamBuilder` share.
+pub(crate) struct AgentConfig<'a> {
+    pub(crate) handler_type: Handlers<'a>,
+    pub(crate) tools: Option<Box<dyn LooperTools>>,
+    pub(crate) server_tools: Vec<ServerTool>,
+    pub(crate) auto_continue: u32,
+    pub(crate) instructions: Option<String>,
+    pub(crate) sub_agents: Vec<SubAgentTool>,
+    pub(crate) handoffs: Vec<Handoff>,
+    pub(crate) hooks: Vec<Arc<dyn LooperHooks>>,
+    pub(crate) checkpoints: Option<(Arc<dyn CheckpointStore>, String)>,
+    pub(crate) generation_settings: GenerationSettings,
+    pub(crate) system_prompt: PromptTemplate,
+    pub(crate) prompt_vars: Vec<(String, Value)>,
+    pub(crate) prompt_contexts: Vec<Arc<dyn PromptContext>>,
+}
+
+impl<'a> AgentConfig<'a> {
+    pub(crate) fn new(handler_type: Handlers<'a>) -> Self {
+        AgentConfig {
+            handler_type,
+            tools: None,
+            server_tools: Vec::new(),
+            auto_continue: 0,
+            instructions: None,
+            sub_agents: Vec::new(),
+            handoffs: Vec::new(),
+            hooks: Vec::new(),
+            checkpoints: None,
+            generation_settings: GenerationSettings::default(),
+            system_prompt: PromptTemplate::default(),
+            prompt_vars: Vec::new(),
+            prompt_contexts: Vec::new(),
+        }
+    }
+}
+
+/// The provider agnostic agent loop shared by `Looper` and `LooperStream`.
+///
+/// A turn alternates between sending one request through the `Provider` and
+/// running the tools it asked for concurrently, until the model answers
+/// without calling a tool. `send_message` runs a whole turn, `start_turn`,
+/// `step` and `finish_turn` drive it one transition at a time. Streaming is
+/// enabled by giving it a `sender`.
+pub struct AgentLoop {
+    provider: Box<dyn Provider>,
+    validator: Arc<ToolArgsValidator>,
+    hooks: Option<Arc<dyn LooperHooks>>,
+    sender: Option<Sender<HandlerToLooperMessage>>,
+    checkpoints: Option<(Arc<dyn CheckpointStore>, String)>,
+    system_prompt: Option<SystemPrompt>,
+    tool_definitions: Vec<LooperToolDefinition>,
+    handoffs: HashMap<String, HandoffTarget>,
+    handed_off_tools: Option<Arc<RuntimeToolSet>>,
+    /// The handoffs taken since the loop was built, in order.
+    handoff_path: Vec<String>,
+    max_continuations: u32,
+    continuations: u32,
+    state: TurnState,
+    turn_id: String,
+    steps: Vec<TurnStep>,
+    pending: Option<PendingToolCalls>,
+    turn_span: Span,
+}
+
+/// The agent a `transfer_to_*` tool call hands the conversation to.
+struct HandoffTarget {
+    agent: AgentLoop,
+    tools: Arc<RuntimeToolSet>,
+}
+
+/// Where a turn driven by `AgentLoop::step` currently is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnState {
+    /// No turn in progress, call `start_turn`.
+    Idle,
+    /// The next `step` sends a model request.
+    Ready,
+    /// The model asked for tools, see `pending_tool_calls`. The next `step`
+    /// runs them and feeds the results back.
+    ToolCallsPending,
+    /// The model answered without calling a tool, call `finish_turn`.
+    Complete,
+}
+
+impl AgentLoop {
+    pub fn new(
+        provider: Box<dyn Provider>,
+        sender: Option<Sender<HandlerToLooperMessage>>,
+    ) -> Self {
+        AgentLoop {
+            provider,
+            validator: Arc::new(ToolArgsValidator::default()),
+            hooks: None,
+            sender,
+            checkpoints: None,
+            system_prompt: None,
+            tool_definitions: Vec::new(),
+            handoffs: HashMap::new(),
+            handed_off_tools: None,
+            handoff_path: Vec::new(),
+            max_continuations: 0,
+            continuations: 0,
+            state: TurnState::Idle,
+            turn_id: String::new(),
+            steps: Vec::new(),
+            pending: None,
+            turn_span: Span::none(),
+        }
+    }
+
+    /// Builds the loop and the tool set it runs from the builder settings.
+    pub(crate) async fn build(
+        config: AgentConfig<'_>,
+        sender: Option<Sender<HandlerToLooperMessage>>,
+    ) -> Result<(Self, Arc<RuntimeToolSet>)> {
+        let mut system_prompt = SystemPrompt::new(config.system_prompt)?;
+        if let Some(instructions) = config.instructions {
+            system_prompt.set_var("instructions", instructions);
+        }
+        if !config.sub_agents.is_empty() {
+            let sub_agents: Vec<Value> = config
+                .sub_agents
+                .iter()
+                .map(|sa| json!({ "name": sa.name(), "description": sa.description() }))
+                .collect();
+            system_prompt.set_var("sub_agent", true);
+            system_prompt.set_var("sub_agents", sub_agents);
+        }
+        for (key, value) in config.prompt_vars {
+            system_prompt.set_var(key, value);
+        }
+        for context in config.prompt_contexts {
+            system_prompt.add_context(context);
+        }
+
+        let tools: Arc<dyn LooperTools> = match config.tools {
+            Some(t) => Arc::from(t),
+            None => Arc::new(EmptyToolSet),
+        };
+        let tools = Arc::new(RuntimeToolSet::new(tools));
+        for sa in config.sub_agents {
+            tools.insert_delegate(Arc::new(sa)).await;
+        }
+
+        let mut handoffs = HashMap::new();
+        for handoff in config.handoffs {
+            let tool_name = handoff.tool_name();
+            let (tool, target) = handoff.into_parts();
+            let (agent, target_tools) = target.into_parts();
+            handoffs.insert(
+                tool_name,
+                HandoffTarget {
+                    agent,
+                    tools: target_tools,
+                },
+            );
+            tools.insert_delegate(Arc::new(tool)).await;
+        }
+        let tool_definitions = tools.get_tools().await;
+
+        let system_message = system_prompt.render(&tool_definitions).await?;
+        let mut agent = AgentLoop::new(
+            create_provider(config.handler_type, &system_message)?,
+            sender,
+        );
+
+        agent
+            .provider
+            .set_generation_settings(config.generation_settings);
+        agent.provider.set_server_tools(&config.server_tools)?;
+        agent.set_tools(tool_definitions);
+        agent.set_hooks(HookChain::into_hooks(config.hooks));
+        agent.system_prompt = Some(system_prompt);
+        agent.handoffs = handoffs;
+        agent.checkpoints = config.checkpoints;
+        agent.max_continuations = config.auto_continue;
+
+        Ok((agent, tools))
+    }
+
+    pub fn model(&self) -> &str {
+        self.provider.model()
+    }
+
+    /// Takes effect from the next model request. History is kept, so the
+    /// model must belong to the same provider.
+    pub fn set_model(&mut self, model: &str) -> Result<()> {
+        self.provider.set_model(model)
+    }
+
+    pub fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.provider.set_generation_settings(settings);
+    }
+
+    pub fn set_tools(&mut self, tools: Vec<LooperToolDefinition>) {
+        self.validator = Arc::new(ToolArgsValidator::new(&tools));
+        self.provider.set_tools(&tools);
+        self.tool_definitions = tools;
+    }
+
+    /// Replaces the system prompt. It is rendered at the start of every
+    /// following turn.
+    pub fn set_system_prompt(&mut self, system_prompt: SystemPrompt) {
+        self.system_prompt = Some(system_prompt);
+    }
+
+    /// Sets a system prompt template variable for the following turns. Does
+    /// nothing for a loop created with `new`, which has no template.
+    pub fn set_prompt_var(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        if let Some(system_prompt) = self.system_prompt.as_mut() {
+            system_prompt.set_var(key, value);
+        }
+    }
+
+    pub fn set_hooks(&mut self, hooks: Option<Arc<dyn LooperHooks>>) {
+        self.hooks = hooks;
+    }
+
+    /// Saves a checkpoint under `key` after every model response and tool
+    /// result, see `resume_turn`.
+    pub fn set_checkpoint_store(
+        &mut self,
+        store: Arc<dyn CheckpointStore>,
+        key: impl Into<String>,
+    ) {
+        self.checkpoints = Some((store, key.into()));
+    }
+
+    /// Runs a whole turn, from the user message to the final response.
+    pub async fn send_message(
+        &mut self,
+        message_history: Option<MessageHistory>,
+        message: &str,
+        tools_runner: Arc<dyn LooperTools>,
+    ) -> Result<TurnResult> {
+        self.start_turn(message_history, message).await?;
+        self.run_to_completion(tools_runner).await
+    }
+
+    /// Resumes and finishes the turn saved in the checkpoint store. `None`
+    /// when there is no unfinished turn.
+    pub async fn resume(
+        &mut self,
+        tools_runner: Arc<dyn LooperTools>,
+    ) -> Result<Option<TurnResult>> {
+        if !self.resume_turn().await? {
+            return Ok(None);
+        }
+
+        self.run_to_completion(tools_runner).await.map(Some)
+    }
+
+    /// Starts a turn to be driven with `step`. Abandons any unfinished turn.
+    pub async fn start_turn(
+        &mut self,
+        message_history: Option<MessageHistory>,
+        message: &str,
+    ) -> Result<()> {
+        if let Some(history) = message_history {
+            self.provider.load_history(history)?;
+        }
+
+        self.render_system_prompt().await?;
+        self.begin(uuid::Uuid::new_v4().to_string(), Vec::new())
+            .await?;
+
+        self.save_checkpoint(TurnProgress::Started {
+            message: message.to_string(),
+        })
+        .await;
+
+        self.provider.push_user_message(message)?;
+        self.state = TurnState::Ready;
+
+        Ok(())
+    }
+
+    /// Restores the turn saved in the checkpoint store so it can be driven
+    /// with `step`. Tool calls that already completed are not run again.
+    /// Returns `false` when there is nothing to resume.
+    pub async fn resume_turn(&mut self) -> Result<bool> {
+        let Some((store, key)) = &self.checkpoints else {
+            return Ok(false);
+        };
+        let Some(checkpoint) = store.load(key).await? else {
+            return Ok(false);
+        };
+
+        // The history is in the format of the agent that saved it, which a
+        // restarted loop has to hand off to again first
+        let Some(handoffs) = checkpoint
+            .handoffs
+            .strip_prefix(self.handoff_path.as_slice())
+        else {
+            bail!(
+                "The checkpoint was saved after the handoffs {:?}, which can't follow the handoffs {:?} already taken",
+                checkpoint.handoffs,
+                self.handoff_path
+            );
+        };
+        for name in handoffs {
+            self.switch_to(name)?;
+        }
+
+        self.provider.load_history(checkpoint.message_history)?;
+        self.render_system_prompt().await?;
+        self.begin(checkpoint.turn_id, checkpoint.steps).await?;
+
+        self.state = match checkpoint.progress {
+            TurnProgress::Started { message } => {
+                self.provider.push_user_message(&message)?;
+                TurnState::Ready
+            }
+            TurnProgress::ToolCallsPending(pending) => {
+                self.pending = Some(pending);
+                TurnState::ToolCallsPending
+            }
+            TurnProgress::Paused => TurnState::Ready,
+            TurnProgress::Complete => TurnState::Complete,
+        };
+
+        Ok(true)
+    }
+
+    /// Advances the turn by one transition: a model request when `Ready`, or
+    /// the pending tool calls when `ToolCallsPending`. A failed step leaves
+    /// the state unchanged so it can be retried.
+    pub async fn step(&mut self, tools_runner: Arc<dyn LooperTools>) -> Result<TurnState> {
+        let span = self.turn_span.clone();
+
+        match self.state {
+            TurnState::Idle => bail!("No turn in progress, call start_turn first"),
+            TurnState::Ready => self.request().instrument(span).await?,
+            TurnState::ToolCallsPending => {
+                self.run_pending_tools(tools_runner)
+                    .instrument(span)
+                    .await?
+            }
+            TurnState::Complete => {}
+        }
+
+        Ok(self.state)
+    }
+
+    pub fn state(&self) -> TurnState {
+        self.state
+    }
+
+    /// The id of the turn in progress, or of the last one once it is over.
+    /// Empty before the first turn.
+    pub fn turn_id(&self) -> &str {
+        &self.turn_id
+    }
+
+    /// The zero based index of the step in progress.
+    pub fn step_index(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// The tool calls the next `step` will run.
+    pub fn pending_tool_calls(&self) -> impl Iterator<Item = &HandlerToLooperToolCallRequest> {
+        self.pending.iter().flat_map(|p| {
+            p.calls
+                .iter()
+                .map(|(call, _)| call)
+                .filter(|call| !p.completed.iter().any(|r| r.id == call.id))
+        })
+    }
+
+    /// The steps completed so far in the current turn.
+    pub fn steps(&self) -> &[TurnStep] {
+        &self.steps
+    }
+
+    /// Ends a `Complete` turn and returns its result.
+    pub async fn finish_turn(&mut self) -> Result<TurnResult> {
+        if self.state != TurnState::Complete {
+            bail!("The turn has not completed yet");
+        }
+
+        let span = std::mem::replace(&mut self.turn_span, Span::none());
+
+        async {
+            if let Some(sender) = &self.sender {
+                sender.send(HandlerToLooperMessage::TurnComplete).await?;
+            }
+
+            let message_history = self.provider.history()?;
+
+            if let Some(hooks) = &self.hooks {
+                hooks.on_turn_complete(&message_history).await;
+            }
+
+            if let Some((store, key)) = &self.checkpoints {
+                store.clear(key).await?;
+            }
+
+            self.state = TurnState::Idle;
+
+            let steps = std::mem::take(&mut self.steps);
+            let final_text = final_text(&steps);
+
+            let mut usage = Usage::default();
+            for step in &steps {
+                usage.add(&step.usage);
+            }
+
+            Ok(TurnResult {
+                steps,
+                final_text,
+                message_history,
+                usage,
+            })
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn request(&mut self) -> Result<()> {
+        let request = match &self.hooks {
+            Some(hooks) => {
+                let mut request = self.provider.hook_request()?;
+                hooks.before_request(&mut request).await?;
+                Some(request)
+            }
+            None => None,
+        };
+
+        let chat_span = ChatSpan::new(
+            self.provider.name(),
+            self.provider.model(),
+            self.steps.len(),
+        );
+        let response = self
+            .provider
+            .send(request, self.sender.as_ref())
+            .instrument(chat_span.span().clone())
+            .await?;
+
+        if let Some(id) = &response.id {
+            chat_span.record_response_id(id);
+        }
+        chat_span.record_usage(&response.usage);
+        chat_span.finish();
+
+        // Sent once the whole response is in, when the cited spans are final
+        if let Some(sender) = &self.sender {
+            for citation in &response.citations {
+                sender
+                    .send(HandlerToLooperMessage::Citation(citation.clone()))
+                    .await?;
+            }
+        }
+
+        if let Some(hooks) = &self.hooks {
+            hooks
+                .after_response(&HookResponse {
+                    thinking: response.thinking.clone(),
+                    text: response.text.clone(),
+                    tool_calls: response
+                        .tool_calls
+                        .iter()
+                        .map(|(call, _)| call.clone())
+                        .collect(),
+                })
+                .await;
+        }
+
+        let stop_reason = response.stop_reason;
+        let mut pending = PendingToolCalls {
+            thinking: response.thinking,
+            text: response.text,
+            calls: response.tool_calls,
+            completed: Vec::new(),
+            server_tool_calls: response.server_tool_calls,
+            citations: response.citations,
+            stop_reason,
+            usage: response.usage,
+        };
+
+        // The last call's arguments were cut off, tell the model why they failed to parse
+        if stop_reason == StopReason::MaxTokens {
+            for error in pending.calls.iter_mut().filter_map(|(_, e)| e.as_mut()) {
+                error.errors.push(TRUNCATED_ARGS.to_string());
+            }
+        }
+
+        if pending.calls.is_empty() {
+            self.steps.push(turn_step(pending));
+
+            match stop_reason {
+                // The paused response is already in history, sending it back resumes it
+                StopReason::Paused => {
+                    self.state = TurnState::Ready;
+                    self.save_checkpoint(TurnProgress::Paused).await;
+                }
+                StopReason::MaxTokens if self.continuations < self.max_continuations => {
+                    self.continuations += 1;
+                    self.save_checkpoint(TurnProgress::Started {
+                        message: CONTINUE_PROMPT.to_string(),
+                    })
+                    .await;
+                    self.provider.push_user_message(CONTINUE_PROMPT)?;
+                    self.state = TurnState::Ready;
+                }
+                _ => {
+                    self.state = TurnState::Complete;
+                    self.save_checkpoint(TurnProgress::Complete).await;
+                }
+            }
+        } else {
+            self.save_checkpoint(TurnProgress::ToolCallsPending(pending.clone()))
+                .await;
+            self.pending = Some(pending);
+            self.state = TurnState::ToolCallsPending;
+        }
+
+        Ok(())
+    }
+
+    async fn run_pending_tools(&mut self, tools_runner: Arc<dyn LooperTools>) -> Result<()> {
+        let calls: Vec<ProviderToolCall> = match &self.pending {
+            Some(p) => p
+                .calls
+                .iter()
+                .filter(|(call, _)| !p.completed.iter().any(|r| r.id == call.id))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // After a handoff earlier in the turn the target's tools run
+        let tools_runner = match &self.handed_off_tools {
+            Some(tools) => tools.clone(),
+            None => tools_runner,
+        };
+        self.run_tools(tools_runner, calls).await?;
+
+        // Pending is only let go of once the results are in history, so a
+        // failed step can be retried or resumed with them
+        if let Some(pending) = &self.pending {
+            let completed = pending.completed.clone();
+            let handoff = completed
+                .iter()
+                .find(|r| self.handoffs.contains_key(&r.name))
+                .map(|r| r.name.clone());
+
+            match handoff {
+                Some(name) => self.hand_off(&name, &completed).await?,
+                None => self.provider.push_tool_results(&completed)?,
+            }
+        }
+
+        if let Some(pending) = self.pending.take() {
+            self.steps.push(turn_step(pending));
+        }
+
+        self.state = TurnState::Ready;
+
+        Ok(())
+    }
+
+    /// Runs `calls` concurrently, recording each result in `pending` (and a
+    /// checkpoint) as soon as it completes.
+    async fn run_tools(
+        &mut self,
+        tools_runner: Arc<dyn LooperTools>,
+        calls: Vec<ProviderToolCall>,
+    ) -> Result<()> {
+        let executor = ToolExecutor::new(
+            tools_runner,
+            self.validator.clone(),
+            self.hooks.clone(),
+            self.sender.clone(),
+        );
+
+        let mut tool_join_set = JoinSet::new();
+        let mut scheduled = HashMap::new();
+
+        for (call, parse_error) in calls {
+            if let Some(sender) = &self.sender {
+                sender
+                    .send(HandlerToLooperMessage::ToolCallExecuting(call.clone()))
+                    .await?;
+            }
+
+            let handle = tool_join_set.spawn(executor.clone().execute(call.clone(), parse_error));
+            scheduled.insert(handle.id(), call);
+        }
+
+        while let Some(result) = tool_join_set.join_next().await {
+            let (call, value) = match result {
+                Ok(r) => r,
+                Err(e) => {
+                    error!(error = %e, "Join error while collecting tool call results");
+
+                    // Every call still needs a result or the provider rejects
+                    // the next request
+                    let Some(call) = scheduled.remove(&e.id()) else {
+                        continue;
+                    };
+                    let value = json!({ "error": format!("Tool call failed: {}", e) });
+                    (call, value)
+                }
+            };
+
+            if let Some(sender) = &self.sender {
+                sender
+                    .send(HandlerToLooperMessage::ToolCallComplete(call.id.clone()))
+                    .await?;
+            }
+
+            let Some(pending) = self.pending.as_mut() else {
+                continue;
+            };
+
+            pending.completed.push(ToolCallRecord {
+                id: call.id,
+                name: call.name,
+                args: call.args,
+                result: value,
+            });
+
+            let progress = TurnProgress::ToolCallsPending(pending.clone());
+            self.save_checkpoint(progress).await;
+        }
+
+        Ok(())
+    }
+
+    /// Makes the target of handoff `name` the active agent, carrying the
+    /// conversation over with every one of `results`. The target gets the history itself when it
+    /// uses the same format, otherwise a transcript of it.
+    async fn hand_off(&mut self, name: &str, results: &[ToolCallRecord]) -> Result<()> {
+        let Some(target) = self.handoffs.get(name) else {
+            bail!("No handoff named {}", name);
+        };
+        let same_format = self.provider.history_format() == target.agent.provider.history_format();
+
+        let (history, transcript) = if same_format {
+            (Some(self.provider.history()?), Vec::new())
+        } else {
+            // Results of the calls made next to the handoff aren't in history yet
+            let mut transcript = self.provider.transcript().await?;
+            transcript.extend(results.iter().map(|r| format!("Tool result: {}", r.result)));
+            (None, transcript)
+        };
+
+        // Nothing fallible is left before the swap, a failure up to here
+        // leaves the handoff to be retried
+        self.switch_to(name)?;
+
+        match history {
+            Some(history) => {
+                self.provider.load_history(history)?;
+                self.provider.push_tool_results(results)?;
+            }
+            None => {
+                self.provider.push_user_message(&format!(
+                    "You are taking over this conversation from another agent. Continue it from where it left off.\n\n<transcript>\n{}\n</transcript>",
+                    transcript.join("\n\n")
+                ))?;
+            }
+        }
+
+        self.render_system_prompt().await
+    }
+
+    /// Makes the handoff target `name` the agent answering, leaving its
+    /// history for the caller to fill in.
+    fn switch_to(&mut self, name: &str) -> Result<()> {
+        let Some(HandoffTarget { agent, tools }) = self.handoffs.remove(name) else {
+            bail!("No handoff named {}", name);
+        };
+
+        // Hooks, checkpoints, auto-continue and the event sender stay, they
+        // belong to the looper rather than the agent answering
+        self.provider = agent.provider;
+        self.validator = agent.validator;
+        self.system_prompt = agent.system_prompt;
+        self.tool_definitions = agent.tool_definitions;
+        self.handoffs = agent.handoffs;
+        self.handed_off_tools = Some(tools);
+        self.handoff_path.push(name.to_string());
+
+        Ok(())
+    }
+
+    /// The tools of the agent a handoff made active, for the owner of the
+    /// previous tools to switch to.
+    pub(crate) fn take_handed_off_tools(&mut self) -> Option<Arc<RuntimeToolSet>> {
+        self.handed_off_tools.take()
+    }
+
+    /// Renders the system prompt with this turn's values. Without one the
+    /// provider keeps the system message it was created with.
+    async fn render_system_prompt(&mut self) -> Result<()> {
+        if let Some(system_prompt) = &self.system_prompt {
+            let system_message = system_prompt.render(&self.tool_definitions).await?;
+            self.provider.set_system_message(&system_message)?;
+        }
+
+        Ok(())
+    }
+
+    async fn begin(&mut self, turn_id: String, steps: Vec<TurnStep>) -> Result<()> {
+        if let Some(sender) = &self.sender {
+            sender
+                .send(HandlerToLooperMessage::TurnStarted(TurnStart {
+                    turn_id: turn_id.clone(),
+                    step: steps.len(),
+                }))
+                .await?;
+        }
+
+        self.turn_id = turn_id;
+        self.steps = steps;
+        self.pending = None;
+        self.continuations = 0;
+        self.turn_span = agent_span(self.provider.name(), self.provider.model());
+
+        Ok(())
+    }
+
+    async fn run_to_completion(
+        &mut self,
+        tools_runner: Arc<dyn LooperTools>,
+    ) -> Result<TurnResult> {
+        while self.step(tools_runner.clone()).await? != TurnState::Complete {}
+
+        self.finish_turn().await
+    }
+
+    /// A failed save only costs durability, so it is logged rather than
+    /// failing the turn.
+    async fn save_checkpoint(&self, progress: TurnProgress) {
+        let Some((store, key)) = &self.checkpoints else {
+            return;
+        };
+
+        let checkpoint = match self.provider.history() {
+            Ok(message_history) => Checkpoint {
+                turn_id: self.turn_id.clone(),
+                handoffs: self.handoff_path.clone(),
+                message_history,
+                steps: self.steps.clone(),
+                progress,
+            },
+            Err(e) => {
+                error!(error = %e, "Failed to read history for checkpoint");
+                return;
+            }
+        };
+
+        if let Err(e) = store.save(key, &checkpoint).await {
+            error!(error = %e, "Failed to save checkpoint");
+        }
+    }
+}
+
+/// The last step's text, joined with the text of the steps it continued.
+fn final_text(steps: &[TurnStep]) -> Option<String> {
+    let last = steps.iter().rposition(|s| s.text.is_some())?;
+    let first = steps[..last]
+        .iter()
+        .rposition(|s| {
+            !s.tool_calls.is_empty()
+                || !matches!(s.stop_reason, StopReason::MaxTokens | StopReason::Paused)
+        })
+        .map_or(0, |i| i + 1);
+
+    Some(
+        steps[first..=last]
+            .iter()
+            .filter_map(|s| s.text.as_deref())
+            .collect(),
+    )
+}
+
+fn turn_step(pending: PendingToolCalls) -> TurnStep {
+    TurnStep {
+        thinking: pending
+            .thinking
+            .into_iter()
+            .map(|content| ThinkingBlock { content })
+            .collect(),
+        text: pending.text,
+        tool_calls: pending.completed,
+        server_tool_calls: pending.server_tool_calls,
+        citations: pending.citations,
+        stop_reason: pending.stop_reason,
+        usage: pending.usage,
+    }
+}
@@ -1,8 +0,0 @@
-pub mod anthropic;
-pub mod anthropic_non_streaming;
-pub mod gemini;
-pub mod gemini_non_streaming;
-pub mod openai_completions;
-pub mod openai_completions_non_streaming;
-pub mod openai_responses;
-pub mod openai_responses_non_streaming;
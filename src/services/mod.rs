@@ -1,8 +1,7 @@
-pub mod chat_handler;
-pub use chat_handler::*;
+pub mod agent_loop;
+pub use agent_loop::*;
 
-pub mod chat_handler_streaming;
-pub use chat_handler_streaming::*;
+pub mod provider;
+pub use provider::*;
 
-pub mod handlers;
-pub use handlers::*;
+pub mod providers;
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    hooks::HookRequest,
+    mapping::transcript::transcript,
+    services::providers::{
+        anthropic::AnthropicProvider, gemini::GeminiProvider,
+        openai_completions::OpenAICompletionsProvider, openai_responses::OpenAIResponsesProvider,
+    },
+    types::{
+        GenerationSettings, HandlerToLooperMessage, HandlerToLooperToolCallRequest, Handlers,
+        LooperToolDefinition, MessageHistory, ServerTool, ToolCallValidationError,
+        turn::{Citation, ServerToolRecord, StopReason, ToolCallRecord},
+    },
+};
+
+pub use crate::types::turn::Usage;
+
+/// A tool call decoded from a model response. Args that could not be parsed
+/// carry the error so the agent loop can report it back to the model.
+pub type ProviderToolCall = (
+    HandlerToLooperToolCallRequest,
+    Option<ToolCallValidationError>,
+);
+
+/// One model response, already appended to the provider's history.
+#[derive(Default)]
+pub struct ProviderResponse {
+    pub id: Option<String>,
+    pub thinking: Vec<String>,
+    pub text: Option<String>,
+    pub tool_calls: Vec<ProviderToolCall>,
+    pub server_tool_calls: Vec<ServerToolRecord>,
+    pub citations: Vec<Citation>,
+    pub stop_reason: StopReason,
+    pub usage: Usage,
+}
+
+/// The provider specific half of the agent loop: building requests, decoding
+/// responses and keeping conversation history in the provider's native
+/// format. Everything else lives once in `AgentLoop`.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// `gen_ai.provider.name` reported on tracing spans
+    fn name(&self) -> &'static str;
+    fn model(&self) -> &str;
+    /// Providers reporting the same format can load each other's history.
+    fn history_format(&self) -> &'static str;
+
+    /// Switches the model used from the next request on, keeping the
+    /// conversation.
+    fn set_model(&mut self, model: &str) -> Result<()>;
+    fn set_generation_settings(&mut self, settings: GenerationSettings);
+
+    fn set_tools(&mut self, tools: &[LooperToolDefinition]);
+    /// Fails for tools the provider does not offer.
+    fn set_server_tools(&mut self, tools: &[ServerTool]) -> Result<()>;
+    fn set_system_message(&mut self, system_message: &str) -> Result<()>;
+
+    /// Replaces the conversation with previously persisted history.
+    fn load_history(&mut self, history: MessageHistory) -> Result<()>;
+    fn history(&self) -> Result<MessageHistory>;
+    /// The conversation as plain lines, for handing it to an agent whose
+    /// provider uses another history format.
+    async fn transcript(&self) -> Result<Vec<String>> {
+        match self.history()? {
+            MessageHistory::Messages(Value::Array(items)) => Ok(transcript(&items)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn push_user_message(&mut self, message: &str) -> Result<()>;
+    fn push_tool_results(&mut self, results: &[ToolCallRecord]) -> Result<()>;
+
+    /// The next request as seen by `LooperHooks::before_request`.
+    fn hook_request(&self) -> Result<HookRequest>;
+
+    /// Sends the next request and appends the response to history.
+    ///
+    /// `request` replaces the provider's own request state when hooks are
+    /// registered. With a `sender` the response is streamed and text,
+    /// thinking and pending tool call deltas are forwarded as they arrive.
+    async fn send(
+        &mut self,
+        request: Option<HookRequest>,
+        sender: Option<&Sender<HandlerToLooperMessage>>,
+    ) -> Result<ProviderResponse>;
+}
+
+pub fn create_provider(handler_type: Handlers, system_message: &str) -> Result<Box<dyn Provider>> {
+    let provider: Box<dyn Provider> = match handler_type {
+        Handlers::OpenAICompletions(m) => {
+            Box::new(OpenAICompletionsProvider::new(m, system_message)?)
+        }
+        Handlers::OpenAIResponses(m) => Box::new(OpenAIResponsesProvider::new(m, system_message)?),
+        Handlers::Anthropic(m) => Box::new(AnthropicProvider::new(m, system_message)?),
+        Handlers::Gemini(m) => Box::new(GeminiProvider::new(m, system_message)?),
+    };
+
+    Ok(provider)
+}
@@ -0,0 +1,781 @@
+use std::collections::HashMap;
+
+use async_anthropic::{
+    Client,
+    types::{
+        ContentBlockDelta, CreateMessagesRequestBuilder, CreateMessagesResponse, Message,
+        MessageContent, MessageContentList, MessageRole, MessagesStreamEvent, Thinking, Tool,
+        ToolResultBuilder, ToolUse,
+    },
+};
+
+use async_trait::async_trait;
+
+use anyhow::{Result, anyhow, bail};
+use futures::StreamExt;
+use reqwest_eventsource::{Event, EventSource, RequestBuilderExt};
+use serde_json::{Value, json};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    hooks::HookRequest,
+    mapping::turn::anthropic::stop_reason,
+    services::{Provider, ProviderResponse, ProviderToolCall, Usage},
+    telemetry::PROVIDER_ANTHROPIC,
+    tools::{ToolExecutor, partial_json},
+    types::{
+        GenerationSettings, HandlerToLooperMessage, HandlerToLooperToolCallRequest,
+        LooperToolDefinition, MessageHistory, PromptCaching, ServerTool, ToolCallArgsDelta,
+        ToolCallStart,
+        turn::{Citation, ServerToolRecord, StopReason, ToolCallRecord},
+    },
+};
+
+type Decoded = (Vec<Block>, Vec<ProviderToolCall>, StopReason, Usage);
+
+const BASE_URL: &str = "https://api.anthropic.com";
+const MESSAGES_PATH: &str = "/v1/messages";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const CODE_EXECUTION_BETA: &str = "code-execution-2025-08-25";
+
+/// A response content block. Server tool blocks have no type in the client,
+/// and it would drop the citations of text blocks, so those are kept as
+/// received, which is also how they have to be sent back.
+enum Block {
+    Content(MessageContent),
+    Raw(Value),
+}
+
+impl Block {
+    fn to_value(&self) -> Result<Value> {
+        Ok(match self {
+            Block::Content(content) => serde_json::to_value(content)?,
+            Block::Raw(block) => block.clone(),
+        })
+    }
+}
+
+pub struct AnthropicProvider {
+    client: Client,
+    /// Streams requests itself, as the client only streams typed requests,
+    /// which have no room for `cache_control` or server tool blocks.
+    http_client: reqwest::Client,
+    api_key: String,
+    beta: Option<&'static str>,
+    model: String,
+    system_message: String,
+    /// Kept as JSON to hold server tool blocks next to typed messages.
+    messages: Vec<Value>,
+    tools: Vec<Tool>,
+    server_tools: Vec<Value>,
+    tool_definitions: Vec<LooperToolDefinition>,
+    settings: GenerationSettings,
+}
+
+impl AnthropicProvider {
+    pub fn new(model: &str, system_message: &str) -> Result<Self> {
+        let api_key = std::env::var("ANTHROPIC_API_KEY")
+            .map_err(|_| anyhow!("ANTHROPIC_API_KEY environment variable must be set"))?;
+
+        Ok(AnthropicProvider {
+            client: Client::default(),
+            http_client: reqwest::Client::new(),
+            api_key,
+            beta: None,
+            model: model.to_string(),
+            system_message: system_message.to_string(),
+            messages: Vec::new(),
+            tools: Vec::new(),
+            server_tools: Vec::new(),
+            tool_definitions: Vec::new(),
+            settings: GenerationSettings::default(),
+        })
+    }
+
+    /// The request body, with the cache breakpoints the settings ask for.
+    fn build_request(&self, request: Option<HookRequest>, stream: bool) -> Result<Value> {
+        let (system_message, messages, tools) = match request {
+            Some(request) => (
+                request.system_message.unwrap_or_default(),
+                request.messages,
+                request.tools.into_iter().map(|t| t.into()).collect(),
+            ),
+            None => (
+                self.system_message.clone(),
+                json!(self.messages),
+                self.tools.clone(),
+            ),
+        };
+
+        // Messages and server tools are added to the JSON below
+        let mut builder = CreateMessagesRequestBuilder::default();
+        builder
+            .model(&self.model)
+            .system(system_message)
+            .messages(Vec::<Message>::new())
+            .tools(tools)
+            .max_tokens(self.settings.max_tokens.unwrap_or(16384) as i32)
+            .thinking(Thinking::Adaptive);
+
+        if let Some(temperature) = self.settings.temperature {
+            builder.temperature(temperature);
+        }
+        if let Some(top_p) = self.settings.top_p {
+            builder.top_p(top_p);
+        }
+
+        let mut body = serde_json::to_value(builder.build()?)?;
+        body["stream"] = json!(stream);
+        body["messages"] = messages;
+
+        if !self.server_tools.is_empty() {
+            let mut tools = self.server_tools.clone();
+            if let Some(Value::Array(function_tools)) = body.get_mut("tools") {
+                tools.append(function_tools);
+            }
+            body["tools"] = json!(tools);
+        }
+
+        add_cache_breakpoints(&mut body, &self.settings.prompt_caching);
+
+        Ok(body)
+    }
+
+    async fn complete(&self, body: Value) -> Result<Decoded> {
+        let mut response: Value = self.client.post(MESSAGES_PATH, body).await?;
+
+        let mut usage = Usage::default();
+        read_cache_usage(&response["usage"], &mut usage);
+
+        let blocks = match response["content"].take() {
+            Value::Array(blocks) => blocks,
+            _ => Vec::new(),
+        };
+
+        let response: CreateMessagesResponse = serde_json::from_value(response)?;
+        let stop_reason = response
+            .stop_reason
+            .as_deref()
+            .map(stop_reason)
+            .unwrap_or_default();
+        if let Some(u) = &response.usage {
+            usage.input_tokens = u.input_tokens.map(u64::from);
+            usage.output_tokens = u.output_tokens.map(u64::from);
+        }
+
+        let mut content = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        for block in blocks {
+            if is_server_block(&block)
+                || block["citations"].as_array().is_some_and(|c| !c.is_empty())
+            {
+                content.push(Block::Raw(block));
+                continue;
+            }
+
+            let block: MessageContent = serde_json::from_value(block)?;
+            if let MessageContent::ToolUse(t) = &block {
+                let call = HandlerToLooperToolCallRequest {
+                    id: t.id.clone(),
+                    name: t.name.clone(),
+                    args: t.input.clone(),
+                };
+                tool_calls.push((call, None));
+            }
+
+            content.push(Block::Content(block));
+        }
+
+        Ok((content, tool_calls, stop_reason, usage))
+    }
+
+    async fn stream(
+        &self,
+        body: Value,
+        sender: &Sender<HandlerToLooperMessage>,
+    ) -> Result<Decoded> {
+        let mut request = self
+            .http_client
+            .post(format!("{}{}", BASE_URL, MESSAGES_PATH))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION);
+        if let Some(beta) = self.beta {
+            request = request.header("anthropic-beta", beta);
+        }
+        let mut events = request.json(&body).eventsource()?;
+
+        let mut usage = Usage::default();
+        let mut finish = StopReason::default();
+        let mut content_blocks = HashMap::new();
+        let mut server_blocks: HashMap<usize, Value> = HashMap::new();
+        let mut tool_input_bufs: HashMap<usize, String> = HashMap::new();
+        let mut signatures: HashMap<usize, String> = HashMap::new();
+        let mut citations: HashMap<usize, Vec<Value>> = HashMap::new();
+        let mut finished_calls: HashMap<usize, ProviderToolCall> = HashMap::new();
+
+        while let Some(result) = next_event(&mut events, &mut usage).await {
+            match result {
+                Ok(StreamEvent::ServerBlockStart { index, block }) => {
+                    server_blocks.insert(index, block);
+                }
+                Ok(StreamEvent::Citation { index, citation }) => {
+                    citations.entry(index).or_default().push(citation);
+                }
+                Ok(StreamEvent::Typed(response)) => match response {
+                    MessagesStreamEvent::MessageStart { message, .. } => {
+                        if let Some(u) = &message.usage {
+                            usage.input_tokens = u.input_tokens.map(u64::from);
+                        }
+                    }
+                    MessagesStreamEvent::MessageDelta { delta, usage: u } => {
+                        if let Some(reason) = &delta.stop_reason {
+                            finish = stop_reason(reason);
+                        }
+                        if let Some(u) = u {
+                            usage.output_tokens = u.output_tokens.map(u64::from);
+                        }
+                    }
+                    MessagesStreamEvent::ContentBlockStart {
+                        index,
+                        content_block,
+                    } => {
+                        if let MessageContent::ToolUse(t) = &content_block {
+                            sender
+                                .send(HandlerToLooperMessage::ToolCallStarted(ToolCallStart {
+                                    id: t.id.clone(),
+                                    name: t.name.clone(),
+                                }))
+                                .await?;
+                        }
+                        content_blocks.insert(index, content_block);
+                    }
+                    MessagesStreamEvent::ContentBlockDelta {
+                        index,
+                        delta: ContentBlockDelta::InputJsonDelta { partial_json },
+                    } if server_blocks.contains_key(&index) => {
+                        tool_input_bufs
+                            .entry(index)
+                            .or_default()
+                            .push_str(&partial_json);
+                    }
+                    MessagesStreamEvent::ContentBlockDelta { index, delta } => {
+                        if let Some(cb) = content_blocks.get_mut(&index) {
+                            match delta {
+                                ContentBlockDelta::TextDelta { text } => {
+                                    if let MessageContent::Text(t) = cb {
+                                        t.text += &text;
+                                        sender
+                                            .send(HandlerToLooperMessage::Assistant(text))
+                                            .await?;
+                                    }
+                                }
+                                ContentBlockDelta::ThinkingDelta { thinking } => {
+                                    if let MessageContent::Thinking(t) = cb {
+                                        t.thinking += &thinking;
+                                        sender
+                                            .send(HandlerToLooperMessage::Thinking(thinking))
+                                            .await?;
+                                    }
+                                }
+                                ContentBlockDelta::SignatureDelta { signature } => {
+                                    if let MessageContent::Thinking(_) = cb {
+                                        signatures.entry(index).or_default().push_str(&signature);
+                                    }
+                                }
+                                ContentBlockDelta::InputJsonDelta { partial_json } => {
+                                    if let MessageContent::ToolUse(t) = cb {
+                                        let raw_input = tool_input_bufs.entry(index).or_default();
+                                        raw_input.push_str(&partial_json);
+
+                                        sender
+                                            .send(HandlerToLooperMessage::ToolCallArgsDelta(
+                                                ToolCallArgsDelta {
+                                                    id: t.id.clone(),
+                                                    partial_args:
+                                                        partial_json::parse_partial_throttled(
+                                                            raw_input,
+                                                            partial_json.len(),
+                                                        ),
+                                                    delta: partial_json,
+                                                },
+                                            ))
+                                            .await?;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    MessagesStreamEvent::ContentBlockStop { index } => {
+                        match content_blocks.get_mut(&index) {
+                            Some(MessageContent::Thinking(_)) => {
+                                sender
+                                    .send(HandlerToLooperMessage::ThinkingComplete)
+                                    .await?;
+                            }
+                            Some(MessageContent::ToolUse(t)) => {
+                                let raw_input = tool_input_bufs.remove(&index).unwrap_or_default();
+                                let call = finish_tool_use(t, &raw_input);
+                                sender
+                                    .send(HandlerToLooperMessage::ToolCallArgsComplete(
+                                        call.0.clone(),
+                                    ))
+                                    .await?;
+                                finished_calls.insert(index, call);
+                            }
+                            _ => (),
+                        }
+
+                        if server_blocks.contains_key(&index) {
+                            let message = finish_server_block(
+                                index,
+                                &mut server_blocks,
+                                &mut tool_input_bufs,
+                            );
+                            sender.send(message).await?;
+                        }
+                    }
+                    _ => (),
+                },
+                // A cut off answer must not pass for a finished one
+                Err(err) => return Err(err),
+            }
+        }
+
+        // Assemble the blocks in order, finishing thinking signatures and any
+        // tool inputs the stream ended in
+        let mut sorted_indices: Vec<usize> = content_blocks
+            .keys()
+            .chain(server_blocks.keys())
+            .copied()
+            .collect();
+        sorted_indices.sort();
+
+        let mut content = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        for index in sorted_indices {
+            let Some(mut block) = content_blocks.remove(&index) else {
+                content.extend(server_blocks.remove(&index).map(Block::Raw));
+                continue;
+            };
+
+            match &mut block {
+                MessageContent::Thinking(t) => {
+                    if let Some(sig) = signatures.remove(&index) {
+                        t.signature = Some(sig);
+                    }
+                }
+                MessageContent::ToolUse(t) => {
+                    let call = match finished_calls.remove(&index) {
+                        Some(call) => call,
+                        None => {
+                            finish_tool_use(t, &tool_input_bufs.remove(&index).unwrap_or_default())
+                        }
+                    };
+                    tool_calls.push(call);
+                }
+                _ => (),
+            }
+
+            if let Some(cited) = citations.remove(&index) {
+                let mut block = serde_json::to_value(&block)?;
+                block["citations"] = cited.into();
+                content.push(Block::Raw(block));
+                continue;
+            }
+
+            content.push(Block::Content(block));
+        }
+
+        Ok((content, tool_calls, finish, usage))
+    }
+}
+
+/// Parses the streamed input of a tool use block into its call.
+fn finish_tool_use(tool_use: &mut ToolUse, raw_input: &str) -> ProviderToolCall {
+    let raw_input = if raw_input.is_empty() {
+        "{}"
+    } else {
+        raw_input
+    };
+
+    let (call, parse_error) = ToolExecutor::parse_call(&tool_use.id, &tool_use.name, raw_input);
+
+    // The API rejects history with non object tool input
+    tool_use.input = match parse_error {
+        Some(_) => json!({}),
+        None => call.args.clone(),
+    };
+
+    (call, parse_error)
+}
+
+/// A stream event, with server tool blocks and citations kept raw as the
+/// client cannot parse them.
+enum StreamEvent {
+    Typed(MessagesStreamEvent),
+    ServerBlockStart { index: usize, block: Value },
+    Citation { index: usize, citation: Value },
+}
+
+fn is_server_block(block: &Value) -> bool {
+    match block["type"].as_str() {
+        Some("server_tool_use") => true,
+        Some(t) => t.ends_with("_tool_result") && t != "tool_result",
+        None => false,
+    }
+}
+
+/// Completes a server tool block once it stopped streaming, returning the
+/// event announcing the call or its result.
+fn finish_server_block(
+    index: usize,
+    server_blocks: &mut HashMap<usize, Value>,
+    tool_input_bufs: &mut HashMap<usize, String>,
+) -> HandlerToLooperMessage {
+    if let Some(block) = server_blocks.get_mut(&index)
+        && block["type"] == "server_tool_use"
+    {
+        if let Some(input) = tool_input_bufs.remove(&index) {
+            block["input"] = serde_json::from_str(&input).unwrap_or_else(|_| json!({}));
+        }
+
+        return HandlerToLooperMessage::ServerToolCall(ServerToolRecord {
+            id: block["id"].as_str().unwrap_or_default().to_string(),
+            name: block["name"].as_str().unwrap_or_default().to_string(),
+            input: block["input"].clone(),
+            output: Value::Null,
+        });
+    }
+
+    let block = &server_blocks[&index];
+    let id = block["tool_use_id"].as_str().unwrap_or_default();
+    let name = server_blocks
+        .values()
+        .find(|b| b["type"] == "server_tool_use" && b["id"] == id)
+        .and_then(|b| b["name"].as_str())
+        .unwrap_or_default();
+
+    HandlerToLooperMessage::ServerToolResult(ServerToolRecord {
+        id: id.to_string(),
+        name: name.to_string(),
+        input: Value::Null,
+        output: block["content"].clone(),
+    })
+}
+
+/// A citation attached to a text block, which spans `start..end` of the
+/// step's text. Web results name a url, documents a title or source.
+fn citation(citation: &Value, start: usize, end: usize) -> Citation {
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| citation[*key].as_str())
+            .map(String::from)
+    };
+
+    Citation {
+        url: field(&["url", "source"]),
+        title: field(&["title", "document_title"]),
+        cited_text: field(&["cited_text"]),
+        start: Some(start),
+        end: Some(end),
+    }
+}
+
+/// Pairs each `server_tool_use` block with the result block answering it.
+fn server_tool_records(blocks: &[Value]) -> Vec<ServerToolRecord> {
+    let mut records: Vec<ServerToolRecord> = Vec::new();
+
+    for block in blocks {
+        if block["type"] == "server_tool_use" {
+            records.push(ServerToolRecord {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                name: block["name"].as_str().unwrap_or_default().to_string(),
+                input: block["input"].clone(),
+                output: Value::Null,
+            });
+        } else if is_server_block(block)
+            && let Some(record) = records
+                .iter_mut()
+                .find(|r| block["tool_use_id"] == r.id.as_str())
+        {
+            record.output = block["content"].clone();
+        }
+    }
+
+    records
+}
+
+/// The next event of a messages stream, reading the cache usage only found in
+/// the raw `message_start`. Ends after an error, as reconnecting would send
+/// the request again.
+async fn next_event(events: &mut EventSource, usage: &mut Usage) -> Option<Result<StreamEvent>> {
+    loop {
+        let message = match events.next().await? {
+            Ok(Event::Open) => continue,
+            Ok(Event::Message(message)) => message,
+            Err(reqwest_eventsource::Error::StreamEnded) => return None,
+            Err(e) => {
+                events.close();
+                return Some(Err(anyhow!("{}", e)));
+            }
+        };
+
+        match message.event.as_str() {
+            "ping" => continue,
+            "error" => {
+                events.close();
+                return Some(Err(anyhow!("{}", message.data)));
+            }
+            _ => (),
+        }
+
+        let mut data: Value = match serde_json::from_str(&message.data) {
+            Ok(data) => data,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        match message.event.as_str() {
+            "message_start" => read_cache_usage(&data["message"]["usage"], usage),
+            "content_block_start" if is_server_block(&data["content_block"]) => {
+                return Some(Ok(StreamEvent::ServerBlockStart {
+                    index: data["index"].as_u64().unwrap_or_default() as usize,
+                    block: data["content_block"].take(),
+                }));
+            }
+            "content_block_delta" if data["delta"]["type"] == "citations_delta" => {
+                return Some(Ok(StreamEvent::Citation {
+                    index: data["index"].as_u64().unwrap_or_default() as usize,
+                    citation: data["delta"]["citation"].take(),
+                }));
+            }
+            _ => (),
+        }
+
+        return Some(
+            serde_json::from_value(data)
+                .map(StreamEvent::Typed)
+                .map_err(Into::into),
+        );
+    }
+}
+
+fn read_cache_usage(usage: &Value, into: &mut Usage) {
+    into.cache_read_tokens = usage["cache_read_input_tokens"].as_u64();
+    into.cache_write_tokens = usage["cache_creation_input_tokens"].as_u64();
+}
+
+/// Marks the system prompt, the last tool and the newest message as cache
+/// breakpoints. A request may have at most four, this uses three.
+fn add_cache_breakpoints(body: &mut Value, caching: &PromptCaching) {
+    let cache_control = match caching.extended_ttl {
+        true => json!({ "type": "ephemeral", "ttl": "1h" }),
+        false => json!({ "type": "ephemeral" }),
+    };
+
+    if caching.system {
+        // Only content blocks take `cache_control`, and empty ones are rejected
+        if let Some(Value::String(text)) = body.get("system").cloned()
+            && !text.is_empty()
+        {
+            body["system"] =
+                json!([{ "type": "text", "text": text, "cache_control": cache_control }]);
+        }
+    }
+
+    if caching.tools
+        && let Some(tool) = body["tools"].as_array_mut().and_then(|t| t.last_mut())
+    {
+        tool["cache_control"] = cache_control.clone();
+    }
+
+    if caching.history
+        && let Some(message) = body["messages"].as_array_mut().and_then(|m| m.last_mut())
+    {
+        if let Value::String(text) = &message["content"] {
+            message["content"] = json!([{ "type": "text", "text": text }]);
+        }
+
+        // Thinking blocks cannot be marked directly
+        let block = message["content"].as_array_mut().and_then(|blocks| {
+            blocks.iter_mut().rev().find(|b| {
+                !matches!(b["type"].as_str(), Some("thinking" | "redacted_thinking"))
+                    && b["text"].as_str() != Some("")
+            })
+        });
+
+        if let Some(block) = block {
+            block["cache_control"] = cache_control;
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_ANTHROPIC
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn history_format(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn set_model(&mut self, model: &str) -> Result<()> {
+        self.model = model.to_string();
+        Ok(())
+    }
+
+    fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.settings = settings;
+    }
+
+    fn set_tools(&mut self, tools: &[LooperToolDefinition]) {
+        self.tools = tools.iter().cloned().map(|t| t.into()).collect();
+        self.tool_definitions = tools.to_vec();
+    }
+
+    fn set_server_tools(&mut self, tools: &[ServerTool]) -> Result<()> {
+        self.server_tools = tools
+            .iter()
+            .map(|tool| match tool {
+                ServerTool::WebSearch => {
+                    Ok(json!({ "type": "web_search_20250305", "name": "web_search" }))
+                }
+                ServerTool::CodeExecution => {
+                    Ok(json!({ "type": "code_execution_20250825", "name": "code_execution" }))
+                }
+                ServerTool::FileSearch { .. } => bail!("Anthropic has no file search tool"),
+            })
+            .collect::<Result<_>>()?;
+
+        // Code execution is still in beta, which the client has to be built with
+        let code_execution = tools.contains(&ServerTool::CodeExecution);
+        self.beta = code_execution.then_some(CODE_EXECUTION_BETA);
+        self.client = match self.beta {
+            Some(beta) => Client::builder()
+                .base_url(BASE_URL)
+                .api_key(self.api_key.clone())
+                .version(ANTHROPIC_VERSION)
+                .beta(beta)
+                .build()?,
+            None => Client::default(),
+        };
+
+        Ok(())
+    }
+
+    fn set_system_message(&mut self, system_message: &str) -> Result<()> {
+        self.system_message = system_message.to_string();
+        Ok(())
+    }
+
+    fn load_history(&mut self, history: MessageHistory) -> Result<()> {
+        if let MessageHistory::Messages(m) = history {
+            self.messages = serde_json::from_value(m)?;
+        }
+
+        Ok(())
+    }
+
+    fn history(&self) -> Result<MessageHistory> {
+        Ok(MessageHistory::Messages(serde_json::to_value(
+            &self.messages,
+        )?))
+    }
+
+    fn push_user_message(&mut self, message: &str) -> Result<()> {
+        self.messages.push(serde_json::to_value(Message {
+            role: MessageRole::User,
+            content: MessageContentList(vec![MessageContent::from(message)]),
+        })?);
+
+        Ok(())
+    }
+
+    fn push_tool_results(&mut self, results: &[ToolCallRecord]) -> Result<()> {
+        for record in results {
+            self.messages.push(serde_json::to_value(Message {
+                role: MessageRole::User,
+                content: MessageContentList(vec![MessageContent::ToolResult(
+                    ToolResultBuilder::default()
+                        .tool_use_id(&record.id)
+                        .content(record.result.to_string())
+                        .build()?,
+                )]),
+            })?);
+        }
+
+        Ok(())
+    }
+
+    fn hook_request(&self) -> Result<HookRequest> {
+        HookRequest::new(
+            Some(&self.system_message),
+            &self.messages,
+            &self.tool_definitions,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        request: Option<HookRequest>,
+        sender: Option<&Sender<HandlerToLooperMessage>>,
+    ) -> Result<ProviderResponse> {
+        let body = self.build_request(request, sender.is_some())?;
+
+        let (content, tool_calls, stop_reason, usage) = match sender {
+            Some(sender) => self.stream(body, sender).await?,
+            None => self.complete(body).await?,
+        };
+
+        let mut response = ProviderResponse {
+            tool_calls,
+            stop_reason,
+            usage,
+            ..Default::default()
+        };
+
+        let content = content
+            .iter()
+            .map(Block::to_value)
+            .collect::<Result<Vec<_>>>()?;
+
+        // Searching splits the answer into a text block per cited passage
+        for block in &content {
+            match block["type"].as_str() {
+                Some("thinking") => response
+                    .thinking
+                    .push(block["thinking"].as_str().unwrap_or_default().to_string()),
+                Some("text") => {
+                    let text = response.text.get_or_insert_default();
+                    let start = text.len();
+                    text.push_str(block["text"].as_str().unwrap_or_default());
+                    let end = text.len();
+
+                    response.citations.extend(
+                        block["citations"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(|c| citation(c, start, end)),
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        if !content.is_empty() {
+            response.server_tool_calls = server_tool_records(&content);
+
+            self.messages
+                .push(json!({ "role": "assistant", "content": content }));
+        }
+
+        Ok(response)
+    }
+}
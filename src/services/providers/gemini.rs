@@ -0,0 +1,810 @@
+use gemini_rust::{
+    Content, ContentBuilder, FunctionCall, FunctionResponse, Gemini, GenerationResponse,
+    GroundingMetadata, Message, Model, Part, Role, Tool,
+};
+
+use async_trait::async_trait;
+
+use anyhow::Result;
+use futures::TryStreamExt;
+use serde_json::{Value, json};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    hooks::HookRequest,
+    mapping::{
+        tools::gemini::to_gemini_tool,
+        turn::gemini::{grounding_citations, stop_reason},
+    },
+    services::{Provider, ProviderResponse, ProviderToolCall, Usage},
+    telemetry::PROVIDER_GEMINI,
+    types::{
+        GenerationSettings, HandlerToLooperMessage, HandlerToLooperToolCallRequest,
+        LooperToolDefinition, MessageHistory, ServerTool, ToolCallStart,
+        turn::{ServerToolRecord, StopReason, ToolCallRecord},
+    },
+};
+
+/// What a single request produced, before it is appended to history.
+struct Decoded {
+    parts: Vec<Part>,
+    tool_calls: Vec<ProviderToolCall>,
+    server_tool_calls: Vec<ServerToolRecord>,
+    grounding: Option<GroundingMetadata>,
+    stop_reason: StopReason,
+    usage: Usage,
+}
+
+const CODE_EXECUTION: &str = "code_execution";
+const GOOGLE_SEARCH: &str = "google_search";
+
+pub struct GeminiProvider {
+    client: Gemini,
+    model: String,
+    system_message: String,
+    messages: Vec<Message>,
+    tool: Option<Tool>,
+    server_tools: Vec<Tool>,
+    tool_definitions: Vec<LooperToolDefinition>,
+    settings: GenerationSettings,
+}
+
+impl GeminiProvider {
+    pub fn new(model: &str, system_message: &str) -> Result<Self> {
+        Ok(GeminiProvider {
+            client: client(model)?,
+            model: model.to_string(),
+            system_message: system_message.to_string(),
+            messages: Vec::new(),
+            tool: None,
+            server_tools: Vec::new(),
+            tool_definitions: Vec::new(),
+            settings: GenerationSettings::default(),
+        })
+    }
+
+    async fn complete(&self, request: Option<HookRequest>) -> Result<Decoded> {
+        let response = self.request_builder(request)?.execute().await?;
+        Ok(decode(&response, self.messages.len()))
+    }
+
+    async fn stream(
+        &self,
+        request: Option<HookRequest>,
+        sender: &Sender<HandlerToLooperMessage>,
+    ) -> Result<Decoded> {
+        let mut stream = self.request_builder(request)?.execute_stream().await?;
+        let message = self.messages.len();
+
+        let mut parts: Vec<Part> = Vec::new();
+        let mut thought = false;
+        let mut tool_calls = Vec::new();
+        let mut google_search = None;
+        let mut grounding = None;
+        let mut code_runs = 0;
+        let mut usage_totals = Usage::default();
+        let mut finish = StopReason::default();
+
+        while let Some(chunk) = stream.try_next().await? {
+            // Usage is cumulative, the last chunk holds the totals
+            if chunk.usage_metadata.is_some() {
+                usage_totals = usage(&chunk);
+            }
+            if let Some(reason) = stop_reason(&chunk) {
+                finish = reason;
+            }
+
+            for candidate in &chunk.candidates {
+                if let Some(record) = candidate
+                    .grounding_metadata
+                    .as_ref()
+                    .and_then(google_search_record)
+                {
+                    if google_search.is_none() {
+                        sender
+                            .send(HandlerToLooperMessage::ServerToolCall(ServerToolRecord {
+                                output: Value::Null,
+                                ..record.clone()
+                            }))
+                            .await?;
+                    }
+                    google_search = Some(record);
+                }
+                if candidate.grounding_metadata.is_some() {
+                    grounding = candidate.grounding_metadata.clone();
+                }
+
+                let Some(candidate_parts) = &candidate.content.parts else {
+                    continue;
+                };
+
+                for part in candidate_parts {
+                    match part {
+                        Part::Text {
+                            text,
+                            thought: is_thought,
+                            ..
+                        } => {
+                            if !text.is_empty() {
+                                let message = if *is_thought == Some(true) {
+                                    thought = true;
+                                    HandlerToLooperMessage::Thinking(text.clone())
+                                } else {
+                                    HandlerToLooperMessage::Assistant(text.clone())
+                                };
+                                sender.send(message).await?;
+                            }
+
+                            merge_part(&mut parts, part);
+                        }
+                        Part::FunctionCall { function_call, .. } => {
+                            // Calls arrive whole, there are no argument deltas
+                            let call = tool_call(function_call, message, parts.len());
+                            sender
+                                .send(HandlerToLooperMessage::ToolCallStarted(ToolCallStart {
+                                    id: call.0.id.clone(),
+                                    name: call.0.name.clone(),
+                                }))
+                                .await?;
+                            sender
+                                .send(HandlerToLooperMessage::ToolCallArgsComplete(call.0.clone()))
+                                .await?;
+
+                            merge_part(&mut parts, part);
+                            tool_calls.push(call);
+                        }
+                        Part::ExecutableCode { executable_code } => {
+                            sender
+                                .send(HandlerToLooperMessage::ServerToolCall(ServerToolRecord {
+                                    id: code_run_id(code_runs),
+                                    name: CODE_EXECUTION.to_string(),
+                                    input: json!(executable_code),
+                                    output: Value::Null,
+                                }))
+                                .await?;
+
+                            code_runs += 1;
+                            merge_part(&mut parts, part);
+                        }
+                        Part::CodeExecutionResult {
+                            code_execution_result,
+                        } => {
+                            sender
+                                .send(HandlerToLooperMessage::ServerToolResult(ServerToolRecord {
+                                    id: code_run_id(code_runs.max(1) - 1),
+                                    name: CODE_EXECUTION.to_string(),
+                                    input: Value::Null,
+                                    output: json!(code_execution_result),
+                                }))
+                                .await?;
+
+                            merge_part(&mut parts, part);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if thought {
+            sender
+                .send(HandlerToLooperMessage::ThinkingComplete)
+                .await?;
+        }
+
+        // Grounding metadata can be repeated, the last chunk's is complete
+        let mut server_tool_calls = Vec::new();
+        if let Some(record) = google_search {
+            sender
+                .send(HandlerToLooperMessage::ServerToolResult(record.clone()))
+                .await?;
+            server_tool_calls.push(record);
+        }
+        server_tool_calls.extend(code_execution_records(&parts));
+
+        Ok(Decoded {
+            parts,
+            tool_calls,
+            server_tool_calls,
+            grounding,
+            stop_reason: finish,
+            usage: usage_totals,
+        })
+    }
+
+    /// Appends a response to history and reports it to the loop.
+    fn record(&mut self, decoded: Decoded) -> ProviderResponse {
+        let Decoded {
+            parts,
+            tool_calls,
+            server_tool_calls,
+            grounding,
+            stop_reason,
+            usage,
+        } = decoded;
+
+        let mut response = ProviderResponse {
+            // Gemini finishes a function call with a plain stop
+            stop_reason: match stop_reason {
+                StopReason::EndTurn if !tool_calls.is_empty() => StopReason::ToolUse,
+                stop_reason => stop_reason,
+            },
+            tool_calls,
+            server_tool_calls,
+            usage,
+            ..Default::default()
+        };
+
+        // Text around code runs comes in several parts, grounding spans cover all of it
+        for part in &parts {
+            if let Part::Text { text, thought, .. } = part {
+                if *thought == Some(true) {
+                    response.thinking.push(text.clone());
+                } else {
+                    response.text.get_or_insert_default().push_str(text);
+                }
+            }
+        }
+
+        if let Some(metadata) = &grounding {
+            response.citations =
+                grounding_citations(metadata, response.text.as_deref().unwrap_or_default());
+        }
+
+        if !parts.is_empty() {
+            self.messages.push(Message {
+                content: Content {
+                    parts: Some(parts),
+                    role: Some(Role::Model),
+                },
+                role: Role::Model,
+            });
+        }
+
+        response
+    }
+
+    fn request_builder(&self, request: Option<HookRequest>) -> Result<ContentBuilder> {
+        let (system_message, messages, tool) = match request {
+            Some(request) => (
+                request.system_message.unwrap_or_default(),
+                serde_json::from_value(request.messages)?,
+                (!request.tools.is_empty()).then(|| to_gemini_tool(request.tools)),
+            ),
+            None => (
+                self.system_message.clone(),
+                self.messages.clone(),
+                self.tool.clone(),
+            ),
+        };
+
+        let mut builder = self
+            .client
+            .generate_content()
+            .with_system_prompt(&system_message)
+            .with_messages(messages)
+            .with_thinking_budget(-1)
+            .with_thoughts_included(true);
+
+        if let Some(max_tokens) = self.settings.max_tokens {
+            builder = builder.with_max_output_tokens(max_tokens as i32);
+        }
+        if let Some(temperature) = self.settings.temperature {
+            builder = builder.with_temperature(temperature);
+        }
+        if let Some(top_p) = self.settings.top_p {
+            builder = builder.with_top_p(top_p);
+        }
+
+        if let Some(tool) = tool {
+            builder = builder.with_tool(tool);
+        }
+        for tool in &self.server_tools {
+            builder = builder.with_tool(tool.clone());
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Adds a streamed part to the parts history will get. Text chunks of the
+/// same kind are merged into one part, but a thought signature ends the
+/// part it is sent with, and has to stay on that part for Gemini to accept
+/// the history back.
+fn merge_part(parts: &mut Vec<Part>, part: &Part) {
+    let Part::Text {
+        text,
+        thought,
+        thought_signature,
+    } = part
+    else {
+        parts.push(part.clone());
+        return;
+    };
+
+    // The signature can come on its own, in a chunk without text
+    if text.is_empty() && thought_signature.is_none() {
+        return;
+    }
+
+    let is_thought = *thought == Some(true);
+    if let Some(Part::Text {
+        text: merged,
+        thought: merged_thought,
+        thought_signature: merged_signature,
+    }) = parts.last_mut()
+        && merged_signature.is_none()
+        && (*merged_thought == Some(true)) == is_thought
+    {
+        merged.push_str(text);
+        merged_signature.clone_from(thought_signature);
+        return;
+    }
+
+    parts.push(Part::Text {
+        text: text.clone(),
+        thought: is_thought.then_some(true),
+        thought_signature: thought_signature.clone(),
+    });
+}
+
+// The model is part of the client
+fn client(model: &str) -> Result<Gemini> {
+    let api_key = std::env::var("GEMINI_API_KEY")
+        .or_else(|_| std::env::var("GOOGLE_API_KEY"))
+        .map_err(|_| {
+            anyhow::anyhow!("GEMINI_API_KEY or GOOGLE_API_KEY environment variable must be set")
+        })?;
+
+    let model_id = if model.starts_with("models/") {
+        Model::Custom(model.to_string())
+    } else {
+        Model::Custom(format!("models/{}", model))
+    };
+
+    Ok(Gemini::with_model(&api_key, model_id)?)
+}
+
+/// What a whole (not streamed) response produced. `message` is where it
+/// will sit in history.
+fn decode(response: &GenerationResponse, message: usize) -> Decoded {
+    let mut parts = Vec::new();
+    let mut tool_calls = Vec::new();
+    let mut server_tool_calls = Vec::new();
+    let mut grounding = None;
+
+    for candidate in &response.candidates {
+        if let Some(candidate_parts) = &candidate.content.parts {
+            for part in candidate_parts {
+                if let Part::FunctionCall { function_call, .. } = part {
+                    tool_calls.push(tool_call(function_call, message, parts.len()));
+                }
+                parts.push(part.clone());
+            }
+        }
+
+        if let Some(metadata) = &candidate.grounding_metadata {
+            server_tool_calls.extend(google_search_record(metadata));
+            grounding = Some(metadata.clone());
+        }
+    }
+
+    server_tool_calls.extend(code_execution_records(&parts));
+
+    Decoded {
+        parts,
+        tool_calls,
+        server_tool_calls,
+        grounding,
+        stop_reason: stop_reason(response).unwrap_or_default(),
+        usage: usage(response),
+    }
+}
+
+/// Gemini doesn't assign ids to function calls. They are named after where
+/// the call sits in history, so the same history always gives the same ids.
+fn tool_call(function_call: &FunctionCall, message: usize, part: usize) -> ProviderToolCall {
+    let call = HandlerToLooperToolCallRequest {
+        id: format!("call_{}_{}", message, part),
+        name: function_call.name.clone(),
+        args: function_call.args.clone(),
+    };
+
+    (call, None)
+}
+
+// Nor to code runs, which are paired with their results in order
+fn code_run_id(index: usize) -> String {
+    format!("{}_{}", CODE_EXECUTION, index)
+}
+
+fn code_execution_records(parts: &[Part]) -> Vec<ServerToolRecord> {
+    let mut records: Vec<ServerToolRecord> = Vec::new();
+
+    for part in parts {
+        match part {
+            Part::ExecutableCode { executable_code } => records.push(ServerToolRecord {
+                id: code_run_id(records.len()),
+                name: CODE_EXECUTION.to_string(),
+                input: json!(executable_code),
+                output: Value::Null,
+            }),
+            Part::CodeExecutionResult {
+                code_execution_result,
+            } => {
+                if let Some(record) = records.last_mut() {
+                    record.output = json!(code_execution_result);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    records
+}
+
+/// Google Search grounding shows up as metadata rather than parts.
+fn google_search_record(metadata: &GroundingMetadata) -> Option<ServerToolRecord> {
+    let queries = metadata.web_search_queries.as_ref()?;
+
+    Some(ServerToolRecord {
+        id: GOOGLE_SEARCH.to_string(),
+        name: GOOGLE_SEARCH.to_string(),
+        input: json!({ "queries": queries }),
+        output: json!(metadata.grounding_chunks),
+    })
+}
+
+fn usage(response: &GenerationResponse) -> Usage {
+    match &response.usage_metadata {
+        Some(usage) => Usage {
+            input_tokens: usage.prompt_token_count.and_then(|t| u64::try_from(t).ok()),
+            output_tokens: usage
+                .candidates_token_count
+                .and_then(|t| u64::try_from(t).ok()),
+            cache_read_tokens: usage
+                .cached_content_token_count
+                .and_then(|t| u64::try_from(t).ok()),
+            cache_write_tokens: None,
+        },
+        None => Usage::default(),
+    }
+}
+
+#[async_trait]
+impl Provider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_GEMINI
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn history_format(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn set_model(&mut self, model: &str) -> Result<()> {
+        self.client = client(model)?;
+        self.model = model.to_string();
+        Ok(())
+    }
+
+    fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.settings = settings;
+    }
+
+    fn set_tools(&mut self, tools: &[LooperToolDefinition]) {
+        self.tool = (!tools.is_empty()).then(|| to_gemini_tool(tools.to_vec()));
+        self.tool_definitions = tools.to_vec();
+    }
+
+    fn set_server_tools(&mut self, tools: &[ServerTool]) -> Result<()> {
+        self.server_tools = tools
+            .iter()
+            .map(|tool| match tool {
+                ServerTool::WebSearch => Tool::google_search(),
+                ServerTool::CodeExecution => Tool::code_execution(),
+                ServerTool::FileSearch { stores } => Tool::file_search(stores.clone(), None),
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    fn set_system_message(&mut self, system_message: &str) -> Result<()> {
+        self.system_message = system_message.to_string();
+        Ok(())
+    }
+
+    fn load_history(&mut self, history: MessageHistory) -> Result<()> {
+        if let MessageHistory::Messages(m) = history {
+            self.messages = serde_json::from_value(m)?;
+        }
+
+        Ok(())
+    }
+
+    fn history(&self) -> Result<MessageHistory> {
+        Ok(MessageHistory::Messages(serde_json::to_value(
+            &self.messages,
+        )?))
+    }
+
+    fn push_user_message(&mut self, message: &str) -> Result<()> {
+        self.messages.push(Message::user(message));
+        Ok(())
+    }
+
+    fn push_tool_results(&mut self, results: &[ToolCallRecord]) -> Result<()> {
+        let parts = results
+            .iter()
+            .map(|record| Part::FunctionResponse {
+                function_response: FunctionResponse {
+                    name: record.name.clone(),
+                    response: Some(record.result.clone()),
+                },
+            })
+            .collect();
+
+        self.messages.push(Message {
+            content: Content {
+                parts: Some(parts),
+                role: Some(Role::User),
+            },
+            role: Role::User,
+        });
+
+        Ok(())
+    }
+
+    fn hook_request(&self) -> Result<HookRequest> {
+        HookRequest::new(
+            Some(&self.system_message),
+            &self.messages,
+            &self.tool_definitions,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        request: Option<HookRequest>,
+        sender: Option<&Sender<HandlerToLooperMessage>>,
+    ) -> Result<ProviderResponse> {
+        let decoded = match sender {
+            Some(sender) => self.stream(request, sender).await?,
+            None => self.complete(request).await?,
+        };
+
+        Ok(self.record(decoded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, thought: bool, signature: Option<&str>) -> Part {
+        Part::Text {
+            text: text.to_string(),
+            thought: thought.then_some(true),
+            thought_signature: signature.map(str::to_string),
+        }
+    }
+
+    fn call(city: &str, signature: Option<&str>) -> Part {
+        Part::FunctionCall {
+            function_call: FunctionCall::new("get_weather", json!({ "city": city })),
+            thought_signature: signature.map(str::to_string),
+        }
+    }
+
+    fn provider() -> GeminiProvider {
+        GeminiProvider {
+            client: Gemini::with_model("test-key", Model::Custom("models/test".to_string()))
+                .unwrap(),
+            model: "test".to_string(),
+            system_message: String::new(),
+            messages: Vec::new(),
+            tool: None,
+            server_tools: Vec::new(),
+            tool_definitions: Vec::new(),
+            settings: GenerationSettings::default(),
+        }
+    }
+
+    fn response(parts: &[Part]) -> GenerationResponse {
+        serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": parts },
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn signatures(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .flat_map(|m| m.content.parts.iter().flatten())
+            .filter_map(|part| match part {
+                Part::Text {
+                    thought_signature, ..
+                }
+                | Part::FunctionCall {
+                    thought_signature, ..
+                } => thought_signature.clone(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn results(response: &ProviderResponse) -> Vec<ToolCallRecord> {
+        response
+            .tool_calls
+            .iter()
+            .map(|(call, _)| ToolCallRecord {
+                id: call.id.clone(),
+                name: call.name.clone(),
+                args: call.args.clone(),
+                result: json!({ "forecast": "sunny" }),
+            })
+            .collect()
+    }
+
+    fn merged(chunks: &[Part]) -> Vec<Part> {
+        let mut parts = Vec::new();
+        for chunk in chunks {
+            merge_part(&mut parts, chunk);
+        }
+        parts
+    }
+
+    #[test]
+    fn trailing_signature_only_chunk_lands_on_the_text_before_it() {
+        let parts = merged(&[
+            text("The answer", false, None),
+            text(" is 4", false, None),
+            text("", false, Some("sig-text")),
+        ]);
+
+        assert_eq!(parts, [text("The answer is 4", false, Some("sig-text"))]);
+    }
+
+    #[test]
+    fn thought_and_text_stay_apart_with_their_own_signatures() {
+        let parts = merged(&[
+            text("Let me ", true, None),
+            text("think", true, Some("sig-thought")),
+            text("Sunny", false, None),
+            text("", false, Some("sig-text")),
+        ]);
+
+        assert_eq!(
+            parts,
+            [
+                text("Let me think", true, Some("sig-thought")),
+                text("Sunny", false, Some("sig-text")),
+            ]
+        );
+    }
+
+    #[test]
+    fn signature_ends_the_part_it_came_with() {
+        let parts = merged(&[
+            text("First", true, Some("sig-1")),
+            text("Second", true, None),
+        ]);
+
+        assert_eq!(
+            parts,
+            [
+                text("First", true, Some("sig-1")),
+                text("Second", true, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_round_tool_use_keeps_every_signature() {
+        // Two rounds as they would be streamed, the second after the tool
+        // results of the first
+        let rounds = [
+            merged(&[
+                text("Paris ", true, None),
+                text("first", true, None),
+                call("Paris", Some("sig-call-1")),
+            ]),
+            merged(&[
+                text("Now Rome", true, Some("sig-thought-2")),
+                call("Rome", Some("sig-call-2")),
+                call("Milan", None),
+                text("", false, None),
+            ]),
+        ];
+
+        assert_eq!(
+            rounds,
+            [
+                vec![
+                    text("Paris first", true, None),
+                    call("Paris", Some("sig-call-1"))
+                ],
+                vec![
+                    text("Now Rome", true, Some("sig-thought-2")),
+                    call("Rome", Some("sig-call-2")),
+                    call("Milan", None),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn signatures_survive_a_history_round_trip() {
+        let mut original = provider();
+        original
+            .push_user_message("Weather in Paris, then Rome?")
+            .unwrap();
+
+        // Whole and streamed responses, with tool results in between
+        let first = decode(
+            &response(&[
+                text("Paris first", true, Some("sig-thought-1")),
+                call("Paris", Some("sig-call-1")),
+            ]),
+            original.messages.len(),
+        );
+        let first = original.record(first);
+        assert_eq!(first.stop_reason, StopReason::ToolUse);
+        original.push_tool_results(&results(&first)).unwrap();
+
+        let second = Decoded {
+            parts: merged(&[
+                text("Now Rome", true, Some("sig-thought-2")),
+                call("Rome", Some("sig-call-2")),
+            ]),
+            tool_calls: Vec::new(),
+            server_tool_calls: Vec::new(),
+            grounding: None,
+            stop_reason: StopReason::EndTurn,
+            usage: Usage::default(),
+        };
+        original.record(second);
+        original
+            .push_tool_results(&[ToolCallRecord {
+                id: "call_3_1".to_string(),
+                name: "get_weather".to_string(),
+                args: json!({ "city": "Rome" }),
+                result: json!({ "forecast": "rain" }),
+            }])
+            .unwrap();
+
+        let third = decode(
+            &response(&[text("Sunny, then rain", false, Some("sig-text-3"))]),
+            original.messages.len(),
+        );
+        original.record(third);
+
+        let expected = [
+            "sig-thought-1",
+            "sig-call-1",
+            "sig-thought-2",
+            "sig-call-2",
+            "sig-text-3",
+        ];
+        assert_eq!(signatures(&original.messages), expected);
+
+        let mut restored = provider();
+        restored.load_history(original.history().unwrap()).unwrap();
+        assert_eq!(signatures(&restored.messages), expected);
+
+        // And once more, as a restarted looper would after a checkpoint
+        let history = serde_json::to_string(&restored.history().unwrap()).unwrap();
+        let mut reloaded = provider();
+        reloaded
+            .load_history(serde_json::from_str(&history).unwrap())
+            .unwrap();
+        assert_eq!(signatures(&reloaded.messages), expected);
+    }
+}
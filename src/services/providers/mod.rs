@@ -0,0 +1,4 @@
+pub mod anthropic;
+pub mod gemini;
+pub mod openai_completions;
+pub mod openai_responses;
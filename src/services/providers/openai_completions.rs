@@ -0,0 +1,427 @@
+use async_openai::{
+    Client,
+    config::OpenAIConfig,
+    types::chat::{
+        ChatCompletionMessageToolCall, ChatCompletionMessageToolCalls,
+        ChatCompletionRequestAssistantMessage, ChatCompletionRequestMessage,
+        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessage,
+        ChatCompletionRequestUserMessageArgs, ChatCompletionStreamOptions, ChatCompletionTools,
+        CompletionUsage, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+        ReasoningEffort,
+    },
+};
+
+use async_trait::async_trait;
+
+use anyhow::{Result, bail};
+use futures::StreamExt;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    hooks::HookRequest,
+    mapping::turn::openai_completions::citations,
+    services::{Provider, ProviderResponse, ProviderToolCall, Usage},
+    telemetry::PROVIDER_OPENAI,
+    tools::{ToolExecutor, partial_json},
+    types::{
+        GenerationSettings, HandlerToLooperMessage, LooperToolDefinition, MessageHistory,
+        ServerTool, ToolCallArgsDelta, ToolCallStart,
+        turn::{Citation, StopReason, ToolCallRecord},
+    },
+};
+
+/// What a single completion produced, before it is appended to history.
+#[derive(Default)]
+struct Decoded {
+    id: Option<String>,
+    text: Option<String>,
+    tool_calls: Vec<ChatCompletionMessageToolCalls>,
+    citations: Vec<Citation>,
+    stop_reason: StopReason,
+    usage: Usage,
+}
+
+pub struct OpenAICompletionsProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    messages: Vec<ChatCompletionRequestMessage>,
+    tools: Vec<ChatCompletionTools>,
+    tool_definitions: Vec<LooperToolDefinition>,
+    settings: GenerationSettings,
+}
+
+impl OpenAICompletionsProvider {
+    pub fn new(model: &str, system_message: &str) -> Result<Self> {
+        let system_message = ChatCompletionRequestSystemMessageArgs::default()
+            .content(system_message)
+            .build()?
+            .into();
+
+        Ok(OpenAICompletionsProvider {
+            client: Client::new(),
+            model: model.to_string(),
+            messages: vec![system_message],
+            tools: Vec::new(),
+            tool_definitions: Vec::new(),
+            settings: GenerationSettings::default(),
+        })
+    }
+
+    fn build_request(
+        &self,
+        request: Option<HookRequest>,
+        stream: bool,
+    ) -> Result<CreateChatCompletionRequest> {
+        let (messages, tools) = match request {
+            Some(request) => (
+                serde_json::from_value(request.messages)?,
+                request
+                    .tools
+                    .into_iter()
+                    .map(|t| ChatCompletionTools::Function(t.into()))
+                    .collect(),
+            ),
+            None => (self.messages.clone(), self.tools.clone()),
+        };
+
+        let mut args = CreateChatCompletionRequestArgs::default();
+        args.model(&self.model)
+            .max_completion_tokens(self.settings.max_tokens.unwrap_or(50000))
+            .messages(messages)
+            .tools(tools)
+            .reasoning_effort(
+                self.settings
+                    .reasoning_effort
+                    .map_or(ReasoningEffort::Low, Into::into),
+            );
+
+        if let Some(temperature) = self.settings.temperature {
+            args.temperature(temperature);
+        }
+        if let Some(top_p) = self.settings.top_p {
+            args.top_p(top_p);
+        }
+
+        // Usage is only reported on the final chunk when asked for
+        if stream {
+            args.stream_options(ChatCompletionStreamOptions {
+                include_usage: Some(true),
+                include_obfuscation: None,
+            });
+        }
+
+        Ok(args.build()?)
+    }
+
+    async fn complete(&self, request: CreateChatCompletionRequest) -> Result<Decoded> {
+        let response = self.client.chat().create(request).await?;
+
+        let mut decoded = Decoded {
+            id: Some(response.id),
+            usage: response.usage.as_ref().map(usage).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        if let Some(choice) = response.choices.into_iter().next() {
+            decoded.citations = citations(&choice.message);
+            decoded.text = choice.message.content;
+            decoded.stop_reason = choice.finish_reason.map(Into::into).unwrap_or_default();
+
+            if has_tool_calls(decoded.stop_reason) {
+                decoded.tool_calls = choice.message.tool_calls.unwrap_or_default();
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    async fn stream(
+        &self,
+        request: CreateChatCompletionRequest,
+        sender: &Sender<HandlerToLooperMessage>,
+    ) -> Result<Decoded> {
+        let mut stream = self.client.chat().create_stream(request).await?;
+        let mut decoded = Decoded::default();
+        let mut assistant_res_buf = Vec::new();
+        let mut tool_calls: Vec<ChatCompletionMessageToolCall> = Vec::new();
+
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(response) => {
+                    // Only the final chunk carries usage
+                    if let Some(usage) = &response.usage {
+                        decoded.id = Some(response.id.clone());
+                        decoded.usage = self::usage(usage);
+                    }
+
+                    for choice in response.choices.into_iter() {
+                        // handle text chunk
+                        if let Some(content) = choice.delta.content {
+                            assistant_res_buf.push(content.clone());
+                            sender
+                                .send(HandlerToLooperMessage::Assistant(content))
+                                .await?;
+                        }
+
+                        // handle tool call chunks
+                        if let Some(tool_call_chunks) = choice.delta.tool_calls {
+                            for chunk in tool_call_chunks {
+                                let index = chunk.index as usize;
+
+                                // Ensure we have enough space in the vector
+                                while tool_calls.len() <= index {
+                                    tool_calls.push(ChatCompletionMessageToolCall {
+                                        id: String::new(),
+                                        function: Default::default(),
+                                    });
+                                }
+
+                                // Update the tool call with chunk data
+                                let tool_call = &mut tool_calls[index];
+                                let started = !tool_call.id.is_empty();
+                                if let Some(id) = chunk.id {
+                                    tool_call.id = id;
+                                }
+                                let mut arguments = None;
+                                if let Some(function_chunk) = chunk.function {
+                                    if let Some(name) = function_chunk.name {
+                                        tool_call.function.name = name;
+                                    }
+                                    if let Some(a) = function_chunk.arguments {
+                                        tool_call.function.arguments.push_str(&a);
+                                        arguments = Some(a);
+                                    }
+                                }
+
+                                // The first chunk of a call carries its id and name
+                                if !started && !tool_call.id.is_empty() {
+                                    sender
+                                        .send(HandlerToLooperMessage::ToolCallStarted(
+                                            ToolCallStart {
+                                                id: tool_call.id.clone(),
+                                                name: tool_call.function.name.clone(),
+                                            },
+                                        ))
+                                        .await?;
+                                }
+                                if let Some(delta) = arguments.filter(|a| !a.is_empty()) {
+                                    sender
+                                        .send(HandlerToLooperMessage::ToolCallArgsDelta(
+                                            ToolCallArgsDelta {
+                                                id: tool_call.id.clone(),
+                                                partial_args: partial_json::parse_partial_throttled(
+                                                    &tool_call.function.arguments,
+                                                    delta.len(),
+                                                ),
+                                                delta,
+                                            },
+                                        ))
+                                        .await?;
+                                }
+                            }
+                        }
+
+                        if let Some(reason) = choice.finish_reason {
+                            decoded.stop_reason = reason.into();
+                        }
+                    }
+                }
+                // A cut off answer must not pass for a finished one
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if !assistant_res_buf.is_empty() {
+            decoded.text = Some(assistant_res_buf.join(""));
+        }
+
+        if has_tool_calls(decoded.stop_reason) {
+            decoded.tool_calls = tool_calls.into_iter().map(|tc| tc.into()).collect();
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// A response cut off at `max_tokens` can end inside a tool call. Its calls
+/// are kept, as each needs a result in history, and the cut off arguments
+/// fail to parse.
+fn has_tool_calls(stop_reason: StopReason) -> bool {
+    matches!(stop_reason, StopReason::ToolUse | StopReason::MaxTokens)
+}
+
+fn usage(usage: &CompletionUsage) -> Usage {
+    Usage {
+        input_tokens: Some(usage.prompt_tokens.into()),
+        output_tokens: Some(usage.completion_tokens.into()),
+        cache_read_tokens: usage
+            .prompt_tokens_details
+            .as_ref()
+            .and_then(|d| d.cached_tokens)
+            .map(u64::from),
+        cache_write_tokens: None,
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAICompletionsProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_OPENAI
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn history_format(&self) -> &'static str {
+        "openai_completions"
+    }
+
+    fn set_model(&mut self, model: &str) -> Result<()> {
+        self.model = model.to_string();
+        Ok(())
+    }
+
+    fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.settings = settings;
+    }
+
+    fn set_tools(&mut self, tools: &[LooperToolDefinition]) {
+        self.tools = tools
+            .iter()
+            .cloned()
+            .map(|t| ChatCompletionTools::Function(t.into()))
+            .collect();
+        self.tool_definitions = tools.to_vec();
+    }
+
+    fn set_server_tools(&mut self, tools: &[ServerTool]) -> Result<()> {
+        if let Some(tool) = tools.first() {
+            bail!(
+                "OpenAI Completions does not support server tools, got {:?}",
+                tool
+            );
+        }
+
+        Ok(())
+    }
+
+    fn set_system_message(&mut self, system_message: &str) -> Result<()> {
+        let message = ChatCompletionRequestSystemMessageArgs::default()
+            .content(system_message)
+            .build()?
+            .into();
+
+        // The system message travels with the history, replace it in place
+        match self.messages.first_mut() {
+            Some(first @ ChatCompletionRequestMessage::System(_)) => *first = message,
+            _ => self.messages.insert(0, message),
+        }
+
+        Ok(())
+    }
+
+    fn load_history(&mut self, history: MessageHistory) -> Result<()> {
+        if let MessageHistory::Messages(m) = history {
+            self.messages = serde_json::from_value(m)?;
+        }
+
+        Ok(())
+    }
+
+    fn history(&self) -> Result<MessageHistory> {
+        Ok(MessageHistory::Messages(serde_json::to_value(
+            &self.messages,
+        )?))
+    }
+
+    fn push_user_message(&mut self, message: &str) -> Result<()> {
+        let message = ChatCompletionRequestUserMessageArgs::default()
+            .content(message)
+            .build()?
+            .into();
+
+        self.messages.push(message);
+
+        Ok(())
+    }
+
+    fn push_tool_results(&mut self, results: &[ToolCallRecord]) -> Result<()> {
+        for record in results {
+            self.messages.push(
+                ChatCompletionRequestToolMessage {
+                    content: record.result.to_string().into(),
+                    tool_call_id: record.id.clone(),
+                }
+                .into(),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn hook_request(&self) -> Result<HookRequest> {
+        HookRequest::new(None, &self.messages, &self.tool_definitions)
+    }
+
+    async fn send(
+        &mut self,
+        request: Option<HookRequest>,
+        sender: Option<&Sender<HandlerToLooperMessage>>,
+    ) -> Result<ProviderResponse> {
+        let decoded = match sender {
+            Some(sender) => {
+                let request = self.build_request(request, true)?;
+                self.stream(request, sender).await?
+            }
+            None => {
+                let request = self.build_request(request, false)?;
+                self.complete(request).await?
+            }
+        };
+
+        let tool_calls: Vec<ProviderToolCall> = decoded
+            .tool_calls
+            .iter()
+            .filter_map(|tc| match tc {
+                ChatCompletionMessageToolCalls::Function(f) => Some(ToolExecutor::parse_call(
+                    &f.id,
+                    &f.function.name,
+                    &f.function.arguments,
+                )),
+                _ => None,
+            })
+            .collect();
+
+        // Arguments are only known to be complete once the stream ended
+        if let Some(sender) = sender {
+            for (call, _) in &tool_calls {
+                sender
+                    .send(HandlerToLooperMessage::ToolCallArgsComplete(call.clone()))
+                    .await?;
+            }
+        }
+
+        if decoded.text.is_some() || !decoded.tool_calls.is_empty() {
+            self.messages.push(
+                ChatCompletionRequestAssistantMessage {
+                    content: decoded.text.clone().map(|c| c.into()),
+                    tool_calls: (!decoded.tool_calls.is_empty()).then_some(decoded.tool_calls),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        Ok(ProviderResponse {
+            id: decoded.id,
+            thinking: Vec::new(),
+            text: decoded.text,
+            tool_calls,
+            server_tool_calls: Vec::new(),
+            citations: decoded.citations,
+            stop_reason: decoded.stop_reason,
+            usage: decoded.usage,
+        })
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use async_openai::{
+    Client,
+    config::OpenAIConfig,
+    traits::RequestOptionsBuilder,
+    types::{
+        chat::ReasoningEffort,
+        responses::{
+            Annotation, CodeInterpreterTool, CodeInterpreterToolContainer, CreateResponse,
+            CreateResponseArgs, FileSearchTool, FunctionCallOutput, FunctionCallOutputItemParam,
+            InputItem, InputParam, Item, OutputItem, OutputMessageContent, Reasoning,
+            ReasoningSummary, Response, ResponseStreamEvent, SummaryPart, Tool, WebSearchTool,
+        },
+    },
+};
+
+use async_trait::async_trait;
+
+use futures::StreamExt;
+use serde_json::{Value, json};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    hooks::HookRequest,
+    mapping::{transcript::transcript, turn::byte_offset},
+    services::{Provider, ProviderResponse, Usage},
+    telemetry::PROVIDER_OPENAI,
+    tools::{ToolExecutor, partial_json},
+    types::{
+        GenerationSettings, HandlerToLooperMessage, LooperToolDefinition, MessageHistory,
+        ServerTool, ToolCallArgsDelta, ToolCallStart,
+        turn::{Citation, ServerToolRecord, StopReason, ToolCallRecord},
+    },
+};
+
+/// Conversation state lives server side, only the input not yet sent (the
+/// user message or tool results) is held here.
+pub struct OpenAIResponsesProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    instructions: String,
+    previous_response_id: Option<String>,
+    pending_input: Option<InputParam>,
+    tools: Vec<Tool>,
+    server_tools: Vec<Tool>,
+    tool_definitions: Vec<LooperToolDefinition>,
+    settings: GenerationSettings,
+}
+
+impl OpenAIResponsesProvider {
+    pub fn new(model: &str, system_message: &str) -> Result<Self> {
+        Ok(OpenAIResponsesProvider {
+            client: Client::new(),
+            model: model.to_string(),
+            instructions: system_message.to_string(),
+            previous_response_id: None,
+            pending_input: None,
+            tools: Vec::new(),
+            server_tools: Vec::new(),
+            tool_definitions: Vec::new(),
+            settings: GenerationSettings::default(),
+        })
+    }
+
+    fn build_request(&self, request: Option<HookRequest>) -> Result<CreateResponse> {
+        let (instructions, input, mut tools) = match request {
+            Some(request) => (
+                request.system_message.unwrap_or_default(),
+                serde_json::from_value(request.messages)?,
+                request
+                    .tools
+                    .into_iter()
+                    .map(|t| Tool::Function(t.into()))
+                    .collect(),
+            ),
+            None => (
+                self.instructions.clone(),
+                self.pending_input.clone(),
+                self.tools.clone(),
+            ),
+        };
+
+        tools.extend(self.server_tools.iter().cloned());
+
+        let mut builder = CreateResponseArgs::default();
+        builder
+            .model(&self.model)
+            .tools(tools)
+            .reasoning(Reasoning {
+                effort: Some(
+                    self.settings
+                        .reasoning_effort
+                        .map_or(ReasoningEffort::High, Into::into),
+                ),
+                summary: Some(ReasoningSummary::Concise),
+            })
+            .instructions(instructions);
+
+        if let Some(max_tokens) = self.settings.max_tokens {
+            builder.max_output_tokens(max_tokens);
+        }
+        if let Some(temperature) = self.settings.temperature {
+            builder.temperature(temperature);
+        }
+        if let Some(top_p) = self.settings.top_p {
+            builder.top_p(top_p);
+        }
+
+        if let Some(i) = input {
+            builder.input(i);
+        }
+
+        if let Some(ref prev_id) = self.previous_response_id {
+            builder.previous_response_id(prev_id);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    async fn stream(
+        &self,
+        request: CreateResponse,
+        sender: &Sender<HandlerToLooperMessage>,
+    ) -> Result<Response> {
+        let mut stream = self.client.responses().create_stream(request).await?;
+        let mut response = None;
+        // Argument deltas only name the output item, map it to the call id and
+        // the arguments so far
+        let mut calls: HashMap<u32, (String, String)> = HashMap::new();
+
+        while let Some(event) = stream.next().await {
+            match event {
+                Ok(ResponseStreamEvent::ResponseOutputTextDelta(delta)) => {
+                    sender
+                        .send(HandlerToLooperMessage::Assistant(delta.delta))
+                        .await?;
+                }
+                Ok(ResponseStreamEvent::ResponseReasoningSummaryTextDelta(delta)) => {
+                    sender
+                        .send(HandlerToLooperMessage::Thinking(delta.delta))
+                        .await?;
+                }
+                Ok(ResponseStreamEvent::ResponseReasoningSummaryTextDone(_)) => {
+                    sender
+                        .send(HandlerToLooperMessage::ThinkingComplete)
+                        .await?;
+                }
+                Ok(ResponseStreamEvent::ResponseFunctionCallArgumentsDelta(delta)) => {
+                    if let Some((id, raw_args)) = calls.get_mut(&delta.output_index) {
+                        raw_args.push_str(&delta.delta);
+                        sender
+                            .send(HandlerToLooperMessage::ToolCallArgsDelta(
+                                ToolCallArgsDelta {
+                                    id: id.clone(),
+                                    partial_args: partial_json::parse_partial_throttled(
+                                        raw_args,
+                                        delta.delta.len(),
+                                    ),
+                                    delta: delta.delta,
+                                },
+                            ))
+                            .await?;
+                    }
+                }
+                Ok(ResponseStreamEvent::ResponseOutputItemAdded(added)) => {
+                    if let OutputItem::FunctionCall(fc) = &added.item {
+                        calls.insert(added.output_index, (fc.call_id.clone(), String::new()));
+                        sender
+                            .send(HandlerToLooperMessage::ToolCallStarted(ToolCallStart {
+                                id: fc.call_id.clone(),
+                                name: fc.name.clone(),
+                            }))
+                            .await?;
+                    }
+                    if let Some(mut call) = server_tool_call(&added.item) {
+                        call.output = Value::Null;
+                        sender
+                            .send(HandlerToLooperMessage::ServerToolCall(call))
+                            .await?;
+                    }
+                }
+                Ok(ResponseStreamEvent::ResponseOutputItemDone(done)) => {
+                    if let OutputItem::FunctionCall(fc) = &done.item {
+                        let (call, _) =
+                            ToolExecutor::parse_call(&fc.call_id, &fc.name, &fc.arguments);
+                        sender
+                            .send(HandlerToLooperMessage::ToolCallArgsComplete(call))
+                            .await?;
+                    }
+                    if let Some(call) = server_tool_call(&done.item) {
+                        sender
+                            .send(HandlerToLooperMessage::ServerToolResult(call))
+                            .await?;
+                    }
+                }
+                Ok(ResponseStreamEvent::ResponseCompleted(completed)) => {
+                    response = Some(completed.response);
+                }
+                // Cut off at max_output_tokens or by the content filter
+                Ok(ResponseStreamEvent::ResponseIncomplete(incomplete)) => {
+                    response = Some(incomplete.response);
+                }
+                Ok(_) => {}
+                // A cut off answer must not pass for a finished one
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        response
+            .ok_or_else(|| anyhow!("OpenAI response stream ended before the response completed"))
+    }
+}
+
+/// The server tool call an output item holds, if any. The output is only
+/// complete once the item is done.
+fn server_tool_call(item: &OutputItem) -> Option<ServerToolRecord> {
+    let (id, name, input, output) = match item {
+        OutputItem::WebSearchCall(c) => (&c.id, "web_search", json!(c.action), json!(c.status)),
+        OutputItem::FileSearchCall(c) => (
+            &c.id,
+            "file_search",
+            json!({ "queries": c.queries }),
+            json!(c.results),
+        ),
+        OutputItem::CodeInterpreterCall(c) => (
+            &c.id,
+            "code_interpreter",
+            json!({ "code": c.code }),
+            json!(c.outputs),
+        ),
+        _ => return None,
+    };
+
+    Some(ServerToolRecord {
+        id: id.clone(),
+        name: name.to_string(),
+        input,
+        output,
+    })
+}
+
+fn stop_reason(response: &Response) -> StopReason {
+    let incomplete = response.incomplete_details.as_ref();
+    match incomplete.map(|details| details.reason.as_str()) {
+        Some("max_output_tokens") => return StopReason::MaxTokens,
+        Some("content_filter") => return StopReason::ContentFilter,
+        Some(_) => return StopReason::Other,
+        None => (),
+    }
+
+    let mut stop_reason = StopReason::EndTurn;
+    for item in &response.output {
+        match item {
+            OutputItem::FunctionCall(_) => return StopReason::ToolUse,
+            OutputItem::Message(m)
+                if m.content
+                    .iter()
+                    .any(|c| matches!(c, OutputMessageContent::Refusal(_))) =>
+            {
+                stop_reason = StopReason::Refusal
+            }
+            _ => (),
+        }
+    }
+
+    stop_reason
+}
+
+/// Citations of an output text part starting `offset` bytes into the step's
+/// text. The annotation bodies have private fields, so they are read as JSON.
+fn citations(text: &str, annotations: &[Annotation], offset: usize) -> Vec<Citation> {
+    annotations
+        .iter()
+        .filter_map(|annotation| {
+            let annotation = json!(annotation);
+            let title = match annotation["type"].as_str()? {
+                "url_citation" => annotation["title"].as_str(),
+                "file_citation" | "container_file_citation" => annotation["filename"].as_str(),
+                _ => return None,
+            };
+            // File citations only mark the position the file is cited at
+            let span = |key: &str| {
+                annotation[key]
+                    .as_u64()
+                    .or(annotation["index"].as_u64())
+                    .map(|i| offset + byte_offset(text, i as usize))
+            };
+
+            Some(Citation {
+                url: annotation["url"].as_str().map(String::from),
+                title: title.map(String::from),
+                cited_text: None,
+                start: span("start_index"),
+                end: span("end_index"),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Provider for OpenAIResponsesProvider {
+    fn name(&self) -> &'static str {
+        PROVIDER_OPENAI
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn history_format(&self) -> &'static str {
+        "openai_responses"
+    }
+
+    fn set_model(&mut self, model: &str) -> Result<()> {
+        self.model = model.to_string();
+        Ok(())
+    }
+
+    fn set_generation_settings(&mut self, settings: GenerationSettings) {
+        self.settings = settings;
+    }
+
+    fn set_tools(&mut self, tools: &[LooperToolDefinition]) {
+        self.tools = tools
+            .iter()
+            .cloned()
+            .map(|t| Tool::Function(t.into()))
+            .collect();
+        self.tool_definitions = tools.to_vec();
+    }
+
+    fn set_server_tools(&mut self, tools: &[ServerTool]) -> Result<()> {
+        self.server_tools = tools
+            .iter()
+            .map(|tool| match tool {
+                ServerTool::WebSearch => Tool::WebSearch(WebSearchTool::default()),
+                ServerTool::CodeExecution => Tool::CodeInterpreter(CodeInterpreterTool {
+                    container: CodeInterpreterToolContainer::Auto(Default::default()),
+                }),
+                ServerTool::FileSearch { stores } => Tool::FileSearch(FileSearchTool {
+                    vector_store_ids: stores.clone(),
+                    max_num_results: None,
+                    filters: None,
+                    ranking_options: None,
+                }),
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    fn set_system_message(&mut self, system_message: &str) -> Result<()> {
+        self.instructions = system_message.to_string();
+        Ok(())
+    }
+
+    fn load_history(&mut self, history: MessageHistory) -> Result<()> {
+        // An empty id is what `history` reports before the first response
+        if let MessageHistory::ResponseId(id) = history {
+            self.previous_response_id = (!id.is_empty()).then_some(id);
+        }
+
+        Ok(())
+    }
+
+    fn history(&self) -> Result<MessageHistory> {
+        Ok(MessageHistory::ResponseId(
+            self.previous_response_id.clone().unwrap_or_default(),
+        ))
+    }
+
+    async fn transcript(&self) -> Result<Vec<String>> {
+        let mut items = Vec::new();
+
+        if let Some(id) = &self.previous_response_id {
+            // The input items of a response include those of the earlier
+            // responses it continues
+            let mut after = None;
+            loop {
+                let mut query = vec![("order", "asc".to_string())];
+                if let Some(after) = after.take() {
+                    query.push(("after", after));
+                }
+
+                let page = self
+                    .client
+                    .responses()
+                    .query(&query)?
+                    .list_input_items(id)
+                    .await?;
+                items.extend(page.data.iter().map(|item| json!(item)));
+
+                match page.last_id {
+                    Some(last_id) if page.has_more => after = Some(last_id),
+                    _ => break,
+                }
+            }
+
+            let response = self.client.responses().retrieve(id).await?;
+            items.extend(response.output.iter().map(|item| json!(item)));
+        }
+
+        match &self.pending_input {
+            Some(InputParam::Text(text)) => items.push(json!({ "role": "user", "content": text })),
+            Some(InputParam::Items(input)) => items.extend(input.iter().map(|item| json!(item))),
+            None => {}
+        }
+
+        Ok(transcript(&items))
+    }
+
+    fn push_user_message(&mut self, message: &str) -> Result<()> {
+        self.pending_input = Some(InputParam::Text(message.to_string()));
+        Ok(())
+    }
+
+    fn push_tool_results(&mut self, results: &[ToolCallRecord]) -> Result<()> {
+        let items = results
+            .iter()
+            .map(|record| {
+                InputItem::Item(Item::FunctionCallOutput(FunctionCallOutputItemParam {
+                    call_id: record.id.clone(),
+                    output: FunctionCallOutput::Text(record.result.to_string()),
+                    id: None,
+                    status: None,
+                }))
+            })
+            .collect();
+
+        self.pending_input = Some(InputParam::Items(items));
+        Ok(())
+    }
+
+    fn hook_request(&self) -> Result<HookRequest> {
+        HookRequest::new(
+            Some(&self.instructions),
+            &self.pending_input,
+            &self.tool_definitions,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        request: Option<HookRequest>,
+        sender: Option<&Sender<HandlerToLooperMessage>>,
+    ) -> Result<ProviderResponse> {
+        let request = self.build_request(request)?;
+
+        let response = match sender {
+            Some(sender) => self.stream(request, sender).await?,
+            None => self.client.responses().create(request).await?,
+        };
+
+        // The response now holds the input, continue the conversation from it
+        self.pending_input = None;
+        self.previous_response_id = Some(response.id.clone());
+
+        let mut decoded = ProviderResponse {
+            stop_reason: stop_reason(&response),
+            id: Some(response.id),
+            usage: match &response.usage {
+                Some(usage) => Usage {
+                    input_tokens: Some(usage.input_tokens.into()),
+                    output_tokens: Some(usage.output_tokens.into()),
+                    cache_read_tokens: Some(usage.input_tokens_details.cached_tokens.into()),
+                    cache_write_tokens: None,
+                },
+                None => Usage::default(),
+            },
+            ..Default::default()
+        };
+
+        for item in &response.output {
+            match item {
+                OutputItem::Reasoning(r) => {
+                    for part in &r.summary {
+                        let SummaryPart::SummaryText(s) = part;
+                        decoded.thinking.push(s.text.clone());
+                    }
+                }
+                OutputItem::Message(m) => {
+                    for content in &m.content {
+                        if let OutputMessageContent::OutputText(t) = content {
+                            let text = decoded.text.get_or_insert_default();
+                            decoded.citations.extend(citations(
+                                &t.text,
+                                &t.annotations,
+                                text.len(),
+                            ));
+                            text.push_str(&t.text);
+                        }
+                    }
+                }
+                OutputItem::WebSearchCall(_)
+                | OutputItem::FileSearchCall(_)
+                | OutputItem::CodeInterpreterCall(_) => {
+                    decoded.server_tool_calls.extend(server_tool_call(item));
+                }
+                OutputItem::FunctionCall(fc) => {
+                    decoded.tool_calls.push(ToolExecutor::parse_call(
+                        &fc.call_id,
+                        &fc.name,
+                        &fc.arguments,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,100 @@
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+
+use crate::types::InterfaceEvent;
+
+/// What happens to a subscriber that falls a whole buffer behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowConsumer {
+    /// Waits for it to catch up, which holds up every other consumer and
+    /// eventually the turn itself.
+    #[default]
+    Block,
+    /// Drops its oldest events to make room, see `Subscription::dropped`.
+    DropOldest,
+    /// Stops sending to it. `recv` returns `None` once it has read what was
+    /// already buffered.
+    Disconnect,
+}
+
+/// One consumer's copy of a `LooperStream`'s events, from
+/// `LooperStream::subscribe`.
+pub struct Subscription {
+    receiver: Receiver,
+    dropped: u64,
+}
+
+enum Receiver {
+    Queue(mpsc::Receiver<InterfaceEvent>),
+    Ring(broadcast::Receiver<InterfaceEvent>),
+}
+
+impl Subscription {
+    /// The next event, or `None` once the looper is dropped or this
+    /// subscriber was disconnected.
+    pub async fn recv(&mut self) -> Option<InterfaceEvent> {
+        match &mut self.receiver {
+            Receiver::Queue(receiver) => receiver.recv().await,
+            Receiver::Ring(receiver) => loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some(event),
+                    Err(RecvError::Lagged(skipped)) => self.dropped += skipped,
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+        }
+    }
+
+    /// How many events were dropped because this subscriber fell behind.
+    /// Only `SlowConsumer::DropOldest` drops events.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// The forwarding task's end of a `Subscription`.
+pub(crate) enum Subscriber {
+    Block(mpsc::Sender<InterfaceEvent>),
+    DropOldest(broadcast::Sender<InterfaceEvent>),
+    Disconnect(mpsc::Sender<InterfaceEvent>),
+}
+
+impl Subscriber {
+    pub(crate) fn new(buffer: usize, policy: SlowConsumer) -> (Subscriber, Subscription) {
+        // Neither channel accepts a zero capacity
+        let buffer = buffer.max(1);
+
+        let (subscriber, receiver) = match policy {
+            SlowConsumer::Block => {
+                let (sender, receiver) = mpsc::channel(buffer);
+                (Subscriber::Block(sender), Receiver::Queue(receiver))
+            }
+            SlowConsumer::DropOldest => {
+                let (sender, receiver) = broadcast::channel(buffer);
+                (Subscriber::DropOldest(sender), Receiver::Ring(receiver))
+            }
+            SlowConsumer::Disconnect => {
+                let (sender, receiver) = mpsc::channel(buffer);
+                (Subscriber::Disconnect(sender), Receiver::Queue(receiver))
+            }
+        };
+
+        let subscription = Subscription {
+            receiver,
+            dropped: 0,
+        };
+        (subscriber, subscription)
+    }
+
+    /// `false` once the subscription is gone or was cut off, after which
+    /// the subscriber should be dropped.
+    pub(crate) async fn send(&self, event: InterfaceEvent) -> bool {
+        match self {
+            Subscriber::Block(sender) => sender.send(event).await.is_ok(),
+            Subscriber::DropOldest(sender) => sender.send(event).is_ok(),
+            Subscriber::Disconnect(sender) => sender.try_send(event).is_ok(),
+        }
+    }
+}
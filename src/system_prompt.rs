@@ -0,0 +1,101 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use tera::{Context, Tera};
+
+use crate::types::LooperToolDefinition;
+
+/// The template used when no other is given.
+pub const DEFAULT_SYSTEM_PROMPT: &str = include_str!("../prompts/system_prompt.txt");
+
+const TEMPLATE_NAME: &str = "system_prompt";
+
+/// Where a system prompt template comes from.
+pub enum PromptTemplate {
+    Raw(String),
+    File(PathBuf),
+    /// A preconfigured instance (custom filters, partials to include...) and
+    /// the name of the template in it to render.
+    Tera {
+        tera: Tera,
+        name: String,
+    },
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        PromptTemplate::Raw(DEFAULT_SYSTEM_PROMPT.to_string())
+    }
+}
+
+/// Supplies template values that change from turn to turn, like the current
+/// date or a user profile. Values it inserts replace static ones of the same
+/// name.
+#[async_trait]
+pub trait PromptContext: Send + Sync {
+    async fn extend(&self, context: &mut Context) -> Result<()>;
+}
+
+/// A system prompt template and the values it is rendered with.
+///
+/// The prompt is rendered again at the start of every turn. Besides the
+/// variables set here, templates see `instructions`, `sub_agent` and
+/// `sub_agents` (name and description of each) when sub-agents are attached,
+/// and `tools`, the name, description and parameters of every tool currently
+/// available.
+pub struct SystemPrompt {
+    tera: Tera,
+    name: String,
+    context: Context,
+    dynamic: Vec<Arc<dyn PromptContext>>,
+}
+
+impl SystemPrompt {
+    pub fn new(template: PromptTemplate) -> Result<Self> {
+        let (tera, name) = match template {
+            PromptTemplate::Raw(template) => {
+                let mut tera = Tera::default();
+                tera.add_raw_template(TEMPLATE_NAME, &template)?;
+                (tera, TEMPLATE_NAME.to_string())
+            }
+            PromptTemplate::File(path) => {
+                let mut tera = Tera::default();
+                tera.add_template_file(path, Some(TEMPLATE_NAME))?;
+                (tera, TEMPLATE_NAME.to_string())
+            }
+            PromptTemplate::Tera { tera, name } => (tera, name),
+        };
+
+        Ok(SystemPrompt {
+            tera,
+            name,
+            context: Context::new(),
+            dynamic: Vec::new(),
+        })
+    }
+
+    pub fn set_var(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.context.insert(key, &value.into());
+    }
+
+    pub fn remove_var(&mut self, key: &str) {
+        self.context.remove(key);
+    }
+
+    pub fn add_context(&mut self, context: Arc<dyn PromptContext>) {
+        self.dynamic.push(context);
+    }
+
+    pub async fn render(&self, tools: &[LooperToolDefinition]) -> Result<String> {
+        let mut context = self.context.clone();
+        context.insert("tools", tools);
+
+        for dynamic in &self.dynamic {
+            dynamic.extend(&mut context).await?;
+        }
+
+        Ok(self.tera.render(&self.name, &context)?)
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Instant;
+
+use tracing::{Span, field::Empty, info_span};
+
+use crate::types::turn::Usage;
+
+// `gen_ai.provider.name` values from the OpenTelemetry GenAI semantic conventions
+pub(crate) const PROVIDER_OPENAI: &str = "openai";
+pub(crate) const PROVIDER_ANTHROPIC: &str = "anthropic";
+pub(crate) const PROVIDER_GEMINI: &str = "gcp.gemini";
+
+/// Span around a whole turn, from the user message to the final response.
+pub(crate) fn agent_span(provider: &str, model: &str) -> Span {
+    info_span!(
+        "invoke_agent",
+        otel.name = format!("invoke_agent {}", model),
+        gen_ai.operation.name = "invoke_agent",
+        gen_ai.provider.name = provider,
+        gen_ai.request.model = model,
+    )
+}
+
+/// Span around a single tool call. Created when the call is scheduled so it
+/// parents correctly even though the call runs on a spawned task.
+pub(crate) fn tool_span(name: &str, id: &str) -> Span {
+    info_span!(
+        "execute_tool",
+        otel.name = format!("execute_tool {}", name),
+        gen_ai.operation.name = "execute_tool",
+        gen_ai.tool.name = name,
+        gen_ai.tool.call.id = id,
+        latency_ms = Empty,
+    )
+}
+
+/// Span around one model request in the agent loop.
+///
+/// Only covers the request itself, tool calls are siblings under the turn's
+/// `invoke_agent` span rather than children of the request that asked for them.
+pub(crate) struct ChatSpan {
+    span: Span,
+    started: Instant,
+}
+
+impl ChatSpan {
+    pub(crate) fn new(provider: &str, model: &str, step: usize) -> Self {
+        let span = info_span!(
+            "chat",
+            otel.name = format!("chat {}", model),
+            gen_ai.operation.name = "chat",
+            gen_ai.provider.name = provider,
+            gen_ai.request.model = model,
+            gen_ai.response.id = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            gen_ai.usage.cache_read.input_tokens = Empty,
+            gen_ai.usage.cache_creation.input_tokens = Empty,
+            looper.step = step,
+            latency_ms = Empty,
+        );
+
+        ChatSpan {
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub(crate) fn record_usage(&self, usage: &Usage) {
+        if let Some(t) = usage.input_tokens {
+            self.span.record("gen_ai.usage.input_tokens", t);
+        }
+        if let Some(t) = usage.output_tokens {
+            self.span.record("gen_ai.usage.output_tokens", t);
+        }
+        if let Some(t) = usage.cache_read_tokens {
+            self.span.record("gen_ai.usage.cache_read.input_tokens", t);
+        }
+        if let Some(t) = usage.cache_write_tokens {
+            self.span
+                .record("gen_ai.usage.cache_creation.input_tokens", t);
+        }
+    }
+
+    pub(crate) fn record_response_id(&self, id: &str) {
+        self.span.record("gen_ai.response.id", id);
+    }
+
+    /// Records the request latency and closes the span.
+    pub(crate) fn finish(self) {
+        self.span
+            .record("latency_ms", self.started.elapsed().as_millis() as u64);
+    }
+}
+
+#[cfg(feature = "otel")]
+pub use otlp::*;
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use anyhow::Result;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+    /// Flushes and shuts down the OTLP exporter when dropped. Keep it alive
+    /// for as long as the process should export spans.
+    pub struct OtlpGuard {
+        provider: SdkTracerProvider,
+    }
+
+    impl Drop for OtlpGuard {
+        fn drop(&mut self) {
+            if let Err(e) = self.provider.shutdown() {
+                eprintln!("Failed to shut down OTLP exporter | Error: {}", e);
+            }
+        }
+    }
+
+    /// Installs a global `tracing` subscriber that logs to stderr and exports
+    /// spans over OTLP/HTTP.
+    ///
+    /// The collector endpoint is read from the standard
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` variable and log levels from `RUST_LOG`
+    /// (defaults to `info`).
+    pub fn init_otlp(service_name: impl Into<String>) -> Result<OtlpGuard> {
+        let exporter = SpanExporter::builder().with_http().build()?;
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(service_name.into())
+                    .build(),
+            )
+            .build();
+
+        let otel_layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("looper-rs"));
+
+        tracing_subscriber::registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .with(otel_layer)
+            .try_init()?;
+
+        Ok(OtlpGuard { provider })
+    }
+}
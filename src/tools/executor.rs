@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::Instant};
+
+use serde_json::{Value, json};
+use tokio::sync::mpsc::Sender;
+use tracing::{Instrument, Span, warn};
+
+use crate::{
+    hooks::{LooperHooks, ToolCallDecision},
+    telemetry::tool_span,
+    tools::{LooperTools, ToolArgsValidator},
+    types::{HandlerToLooperMessage, HandlerToLooperToolCallRequest, ToolCallValidationError},
+};
+
+/// Runs a single tool call the same way for every handler: `before_tool_call`
+/// hook, argument validation, the tool itself, then `after_tool_call`.
+///
+/// Cheap to clone, one clone is moved into each spawned tool task.
+#[derive(Clone)]
+pub(crate) struct ToolExecutor {
+    tools_runner: Arc<dyn LooperTools>,
+    validator: Arc<ToolArgsValidator>,
+    hooks: Option<Arc<dyn LooperHooks>>,
+    sender: Option<Sender<HandlerToLooperMessage>>,
+}
+
+impl ToolExecutor {
+    pub(crate) fn new(
+        tools_runner: Arc<dyn LooperTools>,
+        validator: Arc<ToolArgsValidator>,
+        hooks: Option<Arc<dyn LooperHooks>>,
+        sender: Option<Sender<HandlerToLooperMessage>>,
+    ) -> Self {
+        ToolExecutor {
+            tools_runner,
+            validator,
+            hooks,
+            sender,
+        }
+    }
+
+    /// Builds a call from raw (string encoded) args. Args that aren't valid
+    /// JSON become `Value::Null` plus the error to hand to `execute`.
+    pub(crate) fn parse_call(
+        id: &str,
+        name: &str,
+        raw_args: &str,
+    ) -> (
+        HandlerToLooperToolCallRequest,
+        Option<ToolCallValidationError>,
+    ) {
+        let (args, error) = match ToolArgsValidator::parse(id, name, raw_args) {
+            Ok(args) => (args, None),
+            Err(e) => (Value::Null, Some(e)),
+        };
+
+        let call = HandlerToLooperToolCallRequest {
+            id: id.to_string(),
+            name: name.to_string(),
+            args,
+        };
+
+        (call, error)
+    }
+
+    /// Returns the call (with any args rewritten by hooks) and its result.
+    ///
+    /// The tool span is created here, before the future is spawned, so it is
+    /// parented to the turn that scheduled the call.
+    pub(crate) fn execute(
+        self,
+        call: HandlerToLooperToolCallRequest,
+        parse_error: Option<ToolCallValidationError>,
+    ) -> impl Future<Output = (HandlerToLooperToolCallRequest, Value)> + Send + 'static {
+        let span = tool_span(&call.name, &call.id);
+
+        async move {
+            let started = Instant::now();
+            let mut call = call;
+
+            let mut result = match parse_error {
+                Some(e) => self.invalid(e).await,
+                None => self.run(&mut call).await,
+            };
+
+            if let Some(hooks) = &self.hooks {
+                hooks.after_tool_call(&call, &mut result).await;
+            }
+
+            Span::current().record("latency_ms", started.elapsed().as_millis() as u64);
+
+            (call, result)
+        }
+        .instrument(span)
+    }
+
+    async fn run(&self, call: &mut HandlerToLooperToolCallRequest) -> Value {
+        if let Some(hooks) = &self.hooks
+            && let ToolCallDecision::Block(reason) = hooks
+                .before_tool_call(&call.id, &call.name, &mut call.args)
+                .await
+        {
+            warn!(tool = %call.name, %reason, "Tool call blocked by hook");
+            return json!({ "error": format!("Tool call was blocked: {}", reason) });
+        }
+
+        if let Err(e) = self.validator.validate(&call.id, &call.name, &call.args) {
+            return self.invalid(e).await;
+        }
+
+        self.tools_runner
+            .run_tool(call.name.clone(), call.args.clone())
+            .await
+    }
+
+    async fn invalid(&self, error: ToolCallValidationError) -> Value {
+        warn!(tool = %error.name, errors = ?error.errors, "Invalid tool call arguments");
+        let result = error.to_tool_result();
+
+        if let Some(sender) = &self.sender {
+            let _ = sender
+                .send(HandlerToLooperMessage::ToolCallInvalid(error))
+                .await;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::{looper::Looper, tools::LooperTool, types::LooperToolDefinition};
+
+/// A specialist agent the conversation can be transferred to.
+///
+/// The model sees a `transfer_to_{name}` tool. When it calls it, the target's
+/// instructions, tools and model take over and answer within the same turn.
+/// Hooks, the checkpoint store, `auto_continue` and the event stream stay
+/// those of the looper the handoff was registered on, so a `Looper` target
+/// also works for a `LooperStream`, whose events keep flowing through the
+/// same stream.
+pub struct Handoff {
+    name: String,
+    description: String,
+    target: Looper,
+}
+
+impl Handoff {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, target: Looper) -> Self {
+        Handoff {
+            name: name.into(),
+            description: description.into(),
+            target,
+        }
+    }
+
+    pub fn tool_name(&self) -> String {
+        format!("transfer_to_{}", self.name)
+    }
+
+    pub(crate) fn into_parts(self) -> (HandoffTool, Looper) {
+        let tool = HandoffTool {
+            tool_name: self.tool_name(),
+            name: self.name,
+            description: self.description,
+        };
+
+        (tool, self.target)
+    }
+}
+
+/// Only marks the transfer, `AgentLoop` performs it once the call completes.
+pub(crate) struct HandoffTool {
+    tool_name: String,
+    name: String,
+    description: String,
+}
+
+#[async_trait]
+impl LooperTool for HandoffTool {
+    fn get_tool_name(&self) -> String {
+        self.tool_name.clone()
+    }
+
+    fn tool(&self) -> LooperToolDefinition {
+        LooperToolDefinition::default()
+            .set_name(&self.tool_name)
+            .set_description(&format!(
+                "Transfers the conversation to the {} agent, which takes it from here. {}",
+                self.name, self.description
+            ))
+            .set_paramters(json!({ "type": "object", "properties": {} }))
+    }
+
+    async fn execute(&self, _args: &Value) -> Value {
+        json!({ "transferred_to": self.name })
+    }
+}
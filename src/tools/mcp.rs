@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use rmcp::{
+    RoleClient, ServiceError, ServiceExt,
+    model::{CallToolRequestParams, CallToolResult, RawContent},
+    service::RunningService,
+    transport::{StreamableHttpClientTransport, TokioChildProcess},
+};
+use serde_json::{Value, json};
+use tokio::{process::Command, sync::RwLock};
+use tracing::warn;
+
+use crate::{
+    tools::{LooperTool, LooperTools},
+    types::LooperToolDefinition,
+};
+
+const RECONNECT_ATTEMPTS: u32 = 3;
+const RECONNECT_BACKOFF_MS: u64 = 500;
+
+/// How to reach an MCP server.
+#[derive(Debug, Clone)]
+pub enum McpTransport {
+    /// Spawn the server as a child process and talk to it over stdin/stdout.
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+    },
+    /// Connect to a server exposing the streamable HTTP transport.
+    StreamableHttp { url: String },
+}
+
+impl McpTransport {
+    pub fn stdio(
+        command: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        McpTransport::Stdio {
+            command: command.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            env: HashMap::new(),
+        }
+    }
+
+    pub fn http(url: impl Into<String>) -> Self {
+        McpTransport::StreamableHttp { url: url.into() }
+    }
+
+    /// Sets an environment variable for the spawned server. No-op for HTTP.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        if let McpTransport::Stdio { env, .. } = &mut self {
+            env.insert(key.into(), value.into());
+        }
+        self
+    }
+
+    async fn connect(&self) -> Result<RunningService<RoleClient, ()>> {
+        let service = match self {
+            McpTransport::Stdio { command, args, env } => {
+                let mut cmd = Command::new(command);
+                cmd.args(args).envs(env);
+                ().serve(TokioChildProcess::new(cmd)?).await?
+            }
+            McpTransport::StreamableHttp { url } => {
+                ().serve(StreamableHttpClientTransport::from_uri(url.as_str()))
+                    .await?
+            }
+        };
+
+        Ok(service)
+    }
+}
+
+/// A `LooperTools` implementation backed by the tools of an MCP server.
+///
+/// Tools are discovered on connect and re-discovered whenever the connection
+/// is re-established. If the server goes away mid-session the next tool call
+/// transparently reconnects and retries once.
+pub struct McpToolSet {
+    transport: McpTransport,
+    service: RwLock<RunningService<RoleClient, ()>>,
+    definitions: RwLock<Vec<LooperToolDefinition>>,
+    prefix: Option<String>,
+    local_tools: BTreeMap<String, Arc<dyn LooperTool>>,
+}
+
+impl McpToolSet {
+    pub async fn connect(transport: McpTransport) -> Result<Self> {
+        let service = transport.connect().await?;
+        let tools = service.list_all_tools().await?;
+
+        Ok(McpToolSet {
+            transport,
+            service: RwLock::new(service),
+            definitions: RwLock::new(tools.into_iter().map(Into::into).collect()),
+            prefix: None,
+            local_tools: BTreeMap::new(),
+        })
+    }
+
+    /// Namespaces every MCP tool as `{prefix}__{name}` so tools from
+    /// several servers (or local tools) can't collide.
+    pub fn with_tool_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Re-lists the server's tools, picking up any that were added or removed.
+    pub async fn refresh_tools(&self) -> Result<()> {
+        let peer = self.service.read().await.peer().clone();
+        let tools = peer.list_all_tools().await?;
+        *self.definitions.write().await = tools.into_iter().map(Into::into).collect();
+        Ok(())
+    }
+
+    fn exposed_name(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(p) => format!("{}__{}", p, name),
+            None => name.to_string(),
+        }
+    }
+
+    fn server_name<'a>(&self, name: &'a str) -> Option<&'a str> {
+        match &self.prefix {
+            Some(p) => name.strip_prefix(p.as_str())?.strip_prefix("__"),
+            None => Some(name),
+        }
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        let mut service = self.service.write().await;
+
+        // Another caller may have already reconnected while we waited on the lock
+        if !service.is_closed() && !service.peer().is_transport_closed() {
+            return Ok(());
+        }
+
+        let mut last_err = anyhow!("Failed to reconnect to MCP server");
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            match self.transport.connect().await {
+                Ok(s) => {
+                    *service = s;
+                    drop(service);
+                    return self.refresh_tools().await;
+                }
+                Err(e) => {
+                    warn!(
+                        attempt,
+                        max_attempts = RECONNECT_ATTEMPTS,
+                        error = %e,
+                        "MCP reconnect attempt failed"
+                    );
+                    last_err = e;
+                    tokio::time::sleep(Duration::from_millis(
+                        RECONNECT_BACKOFF_MS * attempt as u64,
+                    ))
+                    .await;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn call(&self, name: &str, args: &Value) -> Result<CallToolResult, ServiceError> {
+        let peer = self.service.read().await.peer().clone();
+
+        peer.call_tool(CallToolRequestParams {
+            meta: None,
+            name: name.to_string().into(),
+            arguments: args.as_object().cloned(),
+            task: None,
+        })
+        .await
+    }
+
+    async fn run_mcp_tool(&self, name: &str, args: Value) -> Value {
+        let result = match self.call(name, &args).await {
+            Err(ServiceError::TransportClosed | ServiceError::TransportSend(_)) => {
+                if let Err(e) = self.reconnect().await {
+                    return json!({ "error": format!("MCP server unavailable | Error: {}", e) });
+                }
+                self.call(name, &args).await
+            }
+            other => other,
+        };
+
+        match result {
+            Ok(r) => call_result_to_value(r),
+            Err(e) => json!({ "error": format!("MCP tool call failed | Error: {}", e) }),
+        }
+    }
+}
+
+#[async_trait]
+impl LooperTools for McpToolSet {
+    async fn get_tools(&self) -> Vec<LooperToolDefinition> {
+        let mut tools: Vec<LooperToolDefinition> = self
+            .definitions
+            .read()
+            .await
+            .iter()
+            .map(|t| {
+                let name = self.exposed_name(&t.name);
+                t.clone().set_name(&name)
+            })
+            .collect();
+
+        for t in self.local_tools.values() {
+            tools.push(t.tool());
+        }
+
+        tools
+    }
+
+    async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
+        let tool_name = tool.get_tool_name();
+        self.local_tools.insert(tool_name, tool);
+    }
+
+    async fn run_tool(&self, name: String, args: Value) -> Value {
+        if let Some(tool) = self.local_tools.get(&name) {
+            return tool.execute(&args).await;
+        }
+
+        match self.server_name(&name) {
+            Some(server_name) => self.run_mcp_tool(server_name, args).await,
+            None => json!({"error": format!("Unknown function: {}", name)}),
+        }
+    }
+}
+
+/// Flattens MCP content blocks into the JSON tool result handed to the model.
+///
+/// Structured content wins when the server provides it, otherwise text
+/// blocks are kept as text and other blocks (images, resources) are passed
+/// through in their MCP JSON form.
+fn call_result_to_value(result: CallToolResult) -> Value {
+    let content: Vec<Value> = result
+        .content
+        .iter()
+        .map(|c| match &c.raw {
+            RawContent::Text(t) => Value::String(t.text.clone()),
+            other => serde_json::to_value(other).unwrap_or_default(),
+        })
+        .collect();
+
+    if result.is_error == Some(true) {
+        return json!({ "error": content });
+    }
+
+    match result.structured_content {
+        Some(structured) => structured,
+        None => json!({ "content": content }),
+    }
+}
@@ -4,6 +4,25 @@ pub use sub_agent::*;
 pub mod empty;
 pub use empty::*;
 
+pub mod handoff;
+pub use handoff::*;
+
+pub mod validation;
+pub use validation::*;
+
+pub mod partial_json;
+
+mod executor;
+pub(crate) use executor::*;
+
+mod runtime;
+pub(crate) use runtime::*;
+
+#[cfg(feature = "mcp")]
+pub mod mcp;
+#[cfg(feature = "mcp")]
+pub use mcp::*;
+
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -13,7 +32,9 @@ use crate::types::LooperToolDefinition;
 
 #[async_trait]
 pub trait LooperTool: Send + Sync {
-    async fn execute(&mut self, args: &Value) -> Value;
+    /// Takes `&self` so one tool can serve concurrent calls, keep any
+    /// mutable state behind a lock.
+    async fn execute(&self, args: &Value) -> Value;
     fn tool(&self) -> LooperToolDefinition;
     fn get_tool_name(&self) -> String;
 }
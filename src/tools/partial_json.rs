@@ -0,0 +1,248 @@
+use serde_json::Value;
+
+/// Arguments up to this many bytes are parsed after every delta.
+const PARSE_ALWAYS: usize = 4096;
+
+/// Best effort parse of tool arguments that are still streaming in, for
+/// previews. Open strings, arrays and objects are closed, and a trailing key
+/// or value that can't be completed is dropped. `None` until anything parses.
+pub fn parse_partial(raw_args: &str) -> Option<Value> {
+    let raw_args = raw_args.trim_end();
+    if let Ok(args) = serde_json::from_str(raw_args) {
+        return Some(args);
+    }
+
+    // Usually the first attempt parses, cutting a string value short.
+    // Otherwise the trailing token is incomplete, like a key without its
+    // value or a half written literal, and is dropped a token at a time
+    let mut end = raw_args.len();
+    while end > 0 {
+        if let Ok(args) = serde_json::from_str(&close_partial_json(&raw_args[..end])) {
+            return Some(args);
+        }
+        let cut = raw_args[..end].rfind([',', ':', '[', '{', '"'])?;
+        // An opening bracket stays, closed it is an empty value
+        end = if raw_args[cut..].starts_with(['[', '{']) && cut + 1 < end {
+            cut + 1
+        } else {
+            cut
+        };
+    }
+
+    None
+}
+
+/// `parse_partial` for arguments that just grew by a `delta_len` bytes
+/// delta, throttled so previewing stays linear in the size of the
+/// arguments. Small arguments are parsed after every delta, larger ones
+/// each time they grow by about an eighth. `None` for skipped deltas.
+pub fn parse_partial_throttled(raw_args: &str, delta_len: usize) -> Option<Value> {
+    let after = raw_args.len();
+    let before = after.saturating_sub(delta_len);
+
+    let step = (after.next_power_of_two() / 8).max(PARSE_ALWAYS);
+    if after > PARSE_ALWAYS && before / step == after / step {
+        return None;
+    }
+
+    parse_partial(raw_args)
+}
+
+/// Appends whatever closes the strings, arrays and objects `raw` left open.
+fn close_partial_json(raw: &str) -> String {
+    let mut open = Vec::new();
+    let mut in_string = false;
+    // Where the escape being read starts, and how many hex digits of a
+    // `\u` escape are still to come
+    let mut escape = None;
+    let mut hex_left = 0;
+    // Where a `\u` high surrogate starts while its low half may follow
+    let mut high_surrogate = None;
+
+    for (i, c) in raw.char_indices() {
+        if in_string {
+            match escape {
+                Some(start) if hex_left > 0 => {
+                    hex_left -= 1;
+                    if hex_left == 0 {
+                        escape = None;
+                        let unit = u16::from_str_radix(&raw[start + 2..i + c.len_utf8()], 16);
+                        high_surrogate = match unit {
+                            Ok(0xD800..=0xDBFF) => Some(start),
+                            _ => None,
+                        };
+                    }
+                }
+                Some(_) if c == 'u' => hex_left = 4,
+                Some(_) => {
+                    escape = None;
+                    high_surrogate = None;
+                }
+                None => match c {
+                    '\\' => escape = Some(i),
+                    '"' => {
+                        in_string = false;
+                        high_surrogate = None;
+                    }
+                    _ => high_surrogate = None,
+                },
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => open.push('}'),
+            '[' => open.push(']'),
+            '}' | ']' => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+
+    let mut closed = raw.to_string();
+    if in_string {
+        // Only the escape that hasn't fully arrived goes, along with a high
+        // surrogate still waiting for its pair
+        if let Some(start) = high_surrogate.or(escape) {
+            closed.truncate(start);
+        }
+        closed.push('"');
+    } else {
+        let trimmed = closed.trim_end().trim_end_matches(',').len();
+        closed.truncate(trimmed);
+    }
+    closed.extend(open.iter().rev());
+
+    closed
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn complete_arguments_parse_as_they_are() {
+        assert_eq!(parse_partial(r#"{"a": 1}"#), Some(json!({ "a": 1 })));
+    }
+
+    #[test]
+    fn unterminated_string_is_closed() {
+        assert_eq!(
+            parse_partial(r#"{"path": "a.txt", "content": "fn ma"#),
+            Some(json!({ "path": "a.txt", "content": "fn ma" }))
+        );
+    }
+
+    #[test]
+    fn escapes_inside_an_open_string() {
+        assert_eq!(
+            parse_partial(r#"{"content": "say \"hi"#),
+            Some(json!({ "content": "say \"hi" }))
+        );
+        // A backslash whose escaped character hasn't arrived yet is dropped
+        assert_eq!(
+            parse_partial(r#"{"content": "line\"#),
+            Some(json!({ "content": "line" }))
+        );
+        assert_eq!(
+            parse_partial(r#"{"content": "a\\"#),
+            Some(json!({ "content": "a\\" }))
+        );
+    }
+
+    #[test]
+    fn incomplete_unicode_escapes_drop_only_the_escape() {
+        assert_eq!(
+            parse_partial(r#"{"content": "caf\u00"#),
+            Some(json!({ "content": "caf" }))
+        );
+        assert_eq!(
+            parse_partial(r#"{"content": "caf\u00e9 o"#),
+            Some(json!({ "content": "café o" }))
+        );
+        // Half a surrogate pair can't be decoded on its own
+        assert_eq!(
+            parse_partial(r#"{"content": "hi \ud83d"#),
+            Some(json!({ "content": "hi " }))
+        );
+        assert_eq!(
+            parse_partial(r#"{"content": "hi \ud83d\ude"#),
+            Some(json!({ "content": "hi " }))
+        );
+        assert_eq!(
+            parse_partial(r#"{"content": "hi \ud83d\ude00"#),
+            Some(json!({ "content": "hi 😀" }))
+        );
+    }
+
+    #[test]
+    fn trailing_commas_are_dropped() {
+        assert_eq!(parse_partial(r#"{"a": 1,"#), Some(json!({ "a": 1 })));
+        assert_eq!(parse_partial("[1, 2, "), Some(json!([1, 2])));
+    }
+
+    #[test]
+    fn keys_without_a_value_are_dropped() {
+        assert_eq!(parse_partial(r#"{"a": 1, "b":"#), Some(json!({ "a": 1 })));
+        assert_eq!(parse_partial(r#"{"a": 1, "bb"#), Some(json!({ "a": 1 })));
+    }
+
+    #[test]
+    fn incomplete_literals_are_dropped() {
+        assert_eq!(parse_partial(r#"{"ok": tru"#), Some(json!({})));
+        assert_eq!(parse_partial("[1, nul"), Some(json!([1])));
+    }
+
+    #[test]
+    fn nested_arrays_and_objects_are_closed_in_order() {
+        assert_eq!(
+            parse_partial(r#"{"rows": [[1, 2], [3"#),
+            Some(json!({ "rows": [[1, 2], [3]] }))
+        );
+        assert_eq!(
+            parse_partial(r#"{"a": {"b": [1, {"c": "d"#),
+            Some(json!({ "a": { "b": [1, { "c": "d" }] } }))
+        );
+    }
+
+    #[test]
+    fn nothing_parses_before_the_first_value() {
+        assert_eq!(parse_partial(""), None);
+        assert_eq!(parse_partial("  "), None);
+        assert_eq!(parse_partial("{"), Some(json!({})));
+    }
+
+    #[test]
+    fn small_arguments_are_parsed_after_every_delta() {
+        let raw = r#"{"content": "abc"#;
+        assert!(parse_partial_throttled(raw, 1).is_some());
+    }
+
+    #[test]
+    fn large_arguments_are_parsed_a_bounded_number_of_times() {
+        let mut raw = String::from(r#"{"content": ""#);
+        let mut parses = 0;
+
+        // 1 MiB streamed in 16 byte deltas
+        for _ in 0..(1 << 16) {
+            raw.push_str("0123456789abcdef");
+            if parse_partial_throttled(&raw, 16).is_some() {
+                parses += 1;
+            }
+        }
+
+        // Every delta up to 4 KiB, then about 8 per doubling
+        assert!(parses < PARSE_ALWAYS / 16 + 8 * 10, "{}", parses);
+        assert_eq!(
+            parse_partial(&raw).unwrap()["content"]
+                .as_str()
+                .unwrap()
+                .len(),
+            1 << 20
+        );
+    }
+}
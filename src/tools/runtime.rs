@@ -0,0 +1,104 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use tokio::sync::RwLock;
+
+use crate::{
+    tools::{LooperTool, LooperTools},
+    types::LooperToolDefinition,
+};
+
+/// The tools a built looper runs: the set it was built with, plus tools
+/// added and minus tools removed since. Changes go through `&self` so the
+/// set can be shared with tool executors and the MCP server.
+pub(crate) struct RuntimeToolSet {
+    base: Arc<dyn LooperTools>,
+    /// Sorted so the tool list, and with it the prompt cache, is the same
+    /// from one request to the next.
+    added: RwLock<BTreeMap<String, Arc<dyn LooperTool>>>,
+    removed: RwLock<HashSet<String>>,
+    /// Sub-agent and handoff tools, which only make sense inside the loop.
+    delegates: RwLock<HashSet<String>>,
+}
+
+impl RuntimeToolSet {
+    pub(crate) fn new(base: Arc<dyn LooperTools>) -> Self {
+        RuntimeToolSet {
+            base,
+            added: RwLock::new(BTreeMap::new()),
+            removed: RwLock::new(HashSet::new()),
+            delegates: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Adds a tool, replacing any tool of the same name.
+    pub(crate) async fn insert(&self, tool: Arc<dyn LooperTool>) {
+        let name = tool.get_tool_name();
+        self.removed.write().await.remove(&name);
+        self.delegates.write().await.remove(&name);
+        self.added.write().await.insert(name, tool);
+    }
+
+    /// Adds a tool that hands work to another agent, see `is_delegate`.
+    pub(crate) async fn insert_delegate(&self, tool: Arc<dyn LooperTool>) {
+        let name = tool.get_tool_name();
+        self.insert(tool).await;
+        self.delegates.write().await.insert(name);
+    }
+
+    pub(crate) async fn remove(&self, name: &str) {
+        self.added.write().await.remove(name);
+        self.delegates.write().await.remove(name);
+        self.removed.write().await.insert(name.to_string());
+    }
+
+    /// Whether `name` is a sub-agent or handoff tool rather than one of the
+    /// looper's own tools.
+    pub(crate) async fn is_delegate(&self, name: &str) -> bool {
+        self.delegates.read().await.contains(name)
+    }
+}
+
+#[async_trait]
+impl LooperTools for RuntimeToolSet {
+    async fn get_tools(&self) -> Vec<LooperToolDefinition> {
+        let added = self.added.read().await;
+        let removed = self.removed.read().await;
+
+        let mut tools: Vec<LooperToolDefinition> = self
+            .base
+            .get_tools()
+            .await
+            .into_iter()
+            .filter(|t| !removed.contains(&t.name) && !added.contains_key(&t.name))
+            .collect();
+
+        for t in added.values() {
+            tools.push(t.tool());
+        }
+
+        tools
+    }
+
+    async fn add_tool(&mut self, tool: Arc<dyn LooperTool>) {
+        self.insert(tool).await;
+    }
+
+    async fn run_tool(&self, name: String, args: Value) -> Value {
+        // Not held across the call so tools can be changed while one runs
+        let added = self.added.read().await.get(&name).cloned();
+        if let Some(tool) = added {
+            return tool.execute(&args).await;
+        }
+
+        if self.removed.read().await.contains(&name) {
+            return json!({"error": format!("Unknown function: {}", name)});
+        }
+
+        self.base.run_tool(name, args).await
+    }
+}
@@ -1,34 +1,196 @@
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Result;
 use async_trait::async_trait;
-use serde_json::json;
-use tera::Value;
+use serde_json::{Value, json};
+use tokio::sync::{
+    Mutex,
+    mpsc::{Receiver, Sender},
+};
+
+use crate::{
+    looper::Looper,
+    looper_stream::LooperStream,
+    services::TurnState,
+    tools::LooperTool,
+    types::{InterfaceEvent, LooperToolDefinition},
+};
+
+const DEFAULT_NAME: &str = "spawn_sub_agent";
+const DEFAULT_DESCRIPTION: &str = "
+    Spawns a sub-agent to go and perform various tasks that report back with a high level summary to the caller.
+    Used to avoid pollution of the overall context window.
+";
+
+/// Builds the `Looper` for each call of a fresh context sub-agent.
+#[async_trait]
+pub trait SubAgentFactory: Send + Sync {
+    async fn build(&self) -> Result<Looper>;
+}
+
+#[async_trait]
+impl<F, Fut> SubAgentFactory for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Looper>> + Send,
+{
+    async fn build(&self) -> Result<Looper> {
+        self().await
+    }
+}
+
+enum SubAgent {
+    /// Keeps its history across calls, so calls take turns.
+    Persistent(Mutex<Looper>),
+    /// A new looper per call, so calls run in parallel.
+    Fresh(Arc<dyn SubAgentFactory>),
+    /// Like `Persistent`, with its events forwarded to the parent's stream.
+    Streaming(Mutex<StreamingAgent>),
+}
 
-use crate::{looper::Looper, tools::LooperTool, types::LooperToolDefinition};
+struct StreamingAgent {
+    looper: LooperStream,
+    /// Taken by the first call, which starts forwarding them. Spawning in
+    /// `streaming` would panic outside a runtime.
+    events: Option<Receiver<InterfaceEvent>>,
+}
 
+/// Exposes a `Looper` or `LooperStream` to the main agent as a tool it can
+/// delegate tasks to.
+///
+/// Each sub-agent has its own name, description, tools and model, and any
+/// number can be attached with `sub_agent_tool`.
 pub struct SubAgentTool {
-    looper: Looper,
+    name: String,
+    description: String,
+    agent: SubAgent,
+    parent_events: Arc<OnceLock<Sender<InterfaceEvent>>>,
 }
 
 impl SubAgentTool {
-    pub fn new(looper: Looper) -> Self {
-        SubAgentTool { looper }
+    /// A sub-agent that remembers earlier tasks.
+    pub fn new(name: impl Into<String>, description: impl Into<String>, looper: Looper) -> Self {
+        SubAgentTool {
+            name: name.into(),
+            description: description.into(),
+            agent: SubAgent::Persistent(Mutex::new(looper)),
+            parent_events: Arc::default(),
+        }
+    }
+
+    /// A sub-agent that starts every task from a clean context, with a
+    /// `Looper` built by `factory`.
+    pub fn fresh(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        factory: impl SubAgentFactory + 'static,
+    ) -> Self {
+        SubAgentTool {
+            name: name.into(),
+            description: description.into(),
+            agent: SubAgent::Fresh(Arc::new(factory)),
+            parent_events: Arc::default(),
+        }
+    }
+
+    /// A sub-agent that remembers earlier tasks and whose events show up in
+    /// the parent `LooperStream`'s events, tagged with `name` as their
+    /// `agent_id`. Takes what `LooperStreamBuilder::build` returns.
+    pub fn streaming(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        (looper, events): (LooperStream, Receiver<InterfaceEvent>),
+    ) -> Self {
+        SubAgentTool {
+            name: name.into(),
+            description: description.into(),
+            agent: SubAgent::Streaming(Mutex::new(StreamingAgent {
+                looper,
+                events: Some(events),
+            })),
+            parent_events: Arc::default(),
+        }
+    }
+
+    /// The `spawn_sub_agent` tool attached by the builders' `sub_agent`.
+    pub fn spawn(looper: Looper) -> Self {
+        SubAgentTool::new(DEFAULT_NAME, DEFAULT_DESCRIPTION, looper)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Where a streaming sub-agent's events go. Until this is set, which
+    /// only a streaming parent does, they are dropped.
+    pub(crate) fn forward_events_to(&self, sender: Sender<InterfaceEvent>) {
+        let _ = self.parent_events.set(sender);
+    }
+
+    async fn run(&self, task_description: &str) -> Result<Option<String>> {
+        let result = match &self.agent {
+            SubAgent::Persistent(looper) => looper.lock().await.send(task_description).await?,
+            SubAgent::Fresh(factory) => factory.build().await?.send(task_description).await?,
+            SubAgent::Streaming(agent) => {
+                let mut agent = agent.lock().await;
+                if let Some(events) = agent.events.take() {
+                    tokio::spawn(forward_events(
+                        self.name.clone(),
+                        events,
+                        self.parent_events.clone(),
+                    ));
+                }
+
+                let looper = &mut agent.looper;
+                looper.start_turn(task_description).await?;
+                while looper.step().await? != TurnState::Complete {}
+                looper.finish_turn().await?
+            }
+        };
+
+        Ok(result.final_text)
+    }
+}
+
+/// Tags each event with the sub-agent it came from, one level deeper than
+/// it was. Events of nested sub-agents keep their own `agent_id`.
+async fn forward_events(
+    name: String,
+    mut events: Receiver<InterfaceEvent>,
+    parent_events: Arc<OnceLock<Sender<InterfaceEvent>>>,
+) {
+    while let Some(mut event) = events.recv().await {
+        // Keep draining without a parent so the sub-agent never blocks
+        let Some(sender) = parent_events.get() else {
+            continue;
+        };
+
+        event.depth += 1;
+        event.agent_id.get_or_insert_with(|| name.clone());
+
+        if sender.send(event).await.is_err() {
+            break;
+        }
     }
 }
 
 #[async_trait]
 impl LooperTool for SubAgentTool {
     fn get_tool_name(&self) -> String {
-        "spawn_sub_agent".to_string()
+        self.name.clone()
     }
 
     fn tool(&self) -> LooperToolDefinition {
         LooperToolDefinition::default()
-            .set_name("spawn_sub_agent")
-            .set_description("
-                Spawns a sub-agent to go and perform various tasks that report back with a high level summary to the caller. 
-                Used to avoid pollution of the overall context window.
-
-                *IMPORTANT*: The sub-agent has access to the exact set of tools that you have access to minus the sub-agent tool itself.
-            ")
+            .set_name(&self.name)
+            .set_description(&self.description)
             .set_paramters(json!({
                 "type": "object",
                 "properties": {
@@ -38,21 +200,17 @@ impl LooperTool for SubAgentTool {
             }))
     }
 
-    async fn execute(&mut self, args: &Value) -> Value {
+    async fn execute(&self, args: &Value) -> Value {
         let Some(task_description) = args["task_description"].as_str() else {
             return json!({ "error": "Missing 'task_description' argument" });
         };
 
-        let result = match self.looper.send(task_description).await {
-            Ok(r) => r,
+        match self.run(task_description).await {
+            Ok(Some(ft)) => json!({ "agent_findings": ft }),
+            Ok(None) => json!({ "error": "No agent_findings output were generated" }),
             Err(e) => {
-                return json!({ "error": format!("An error occured when sending message | Error: {}", e) });
+                json!({ "error": format!("An error occured when sending message | Error: {}", e) })
             }
-        };
-
-        match &result.final_text {
-            Some(ft) => json!({ "agent_findings": ft }),
-            None => json!({ "error": "No agent_findings output were generated" }),
         }
     }
 }
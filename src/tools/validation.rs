@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use jsonschema::Validator;
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::types::{LooperToolDefinition, ToolCallValidationError};
+
+/// Validates model generated tool arguments against the JSON Schema declared
+/// in each tool's `LooperToolDefinition::parameters`.
+///
+/// Tools without a compilable schema are passed through unvalidated so a bad
+/// schema never blocks a tool from running.
+#[derive(Default)]
+pub struct ToolArgsValidator {
+    validators: HashMap<String, Validator>,
+}
+
+impl ToolArgsValidator {
+    pub fn new(tools: &[LooperToolDefinition]) -> Self {
+        let mut validators = HashMap::with_capacity(tools.len());
+
+        for tool in tools {
+            match jsonschema::validator_for(&tool.parameters) {
+                Ok(v) => {
+                    validators.insert(tool.name.clone(), v);
+                }
+                Err(e) => {
+                    warn!(
+                        tool = %tool.name,
+                        error = %e,
+                        "Skipping argument validation, invalid schema"
+                    );
+                }
+            }
+        }
+
+        ToolArgsValidator { validators }
+    }
+
+    pub fn validate(
+        &self,
+        id: &str,
+        name: &str,
+        args: &Value,
+    ) -> Result<(), ToolCallValidationError> {
+        let Some(validator) = self.validators.get(name) else {
+            return Ok(());
+        };
+
+        let errors: Vec<String> = validator
+            .iter_errors(args)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{}: {}", path, e)
+                }
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ToolCallValidationError {
+                id: id.to_string(),
+                name: name.to_string(),
+                errors,
+            })
+        }
+    }
+
+    /// Parses raw (string encoded) tool arguments without checking them
+    /// against the schema.
+    ///
+    /// An empty string is treated as an empty object since some providers
+    /// omit arguments entirely for tools without parameters.
+    pub fn parse(id: &str, name: &str, raw_args: &str) -> Result<Value, ToolCallValidationError> {
+        if raw_args.trim().is_empty() {
+            return Ok(json!({}));
+        }
+
+        serde_json::from_str(raw_args).map_err(|e| ToolCallValidationError {
+            id: id.to_string(),
+            name: name.to_string(),
+            errors: vec![format!("Arguments are not valid JSON: {}", e)],
+        })
+    }
+
+    /// Parses raw (string encoded) tool arguments and validates the result.
+    pub fn parse_and_validate(
+        &self,
+        id: &str,
+        name: &str,
+        raw_args: &str,
+    ) -> Result<Value, ToolCallValidationError> {
+        let args = Self::parse(id, name, raw_args)?;
+        self.validate(id, name, &args)?;
+
+        Ok(args)
+    }
+}
+
+impl ToolCallValidationError {
+    /// The tool result handed back to the model in place of running the tool,
+    /// worded so the model can correct its arguments and retry.
+    pub fn to_tool_result(&self) -> Value {
+        json!({
+            "error": format!("Invalid arguments for tool '{}'. The tool was not executed.", self.name),
+            "validation_errors": self.errors,
+            "hint": "Fix the arguments so they match the tool's parameter schema and call the tool again."
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Request settings applied to every model call. `None` keeps the
+/// provider's default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationSettings {
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    /// Only honoured by the OpenAI handlers, Anthropic and Gemini decide how
+    /// long to think on their own.
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Only honoured by the Anthropic handlers, OpenAI and Gemini cache
+    /// repeated prompt prefixes on their own.
+    #[serde(default)]
+    pub prompt_caching: PromptCaching,
+}
+
+/// Which parts of each request get a `cache_control` breakpoint, so later
+/// requests starting with the same prefix read it from the prompt cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptCaching {
+    pub system: bool,
+    pub tools: bool,
+    /// A breakpoint on the newest message, moving forward with every request.
+    pub history: bool,
+    /// Keep cache entries for an hour instead of five minutes. Writes cost
+    /// more.
+    pub extended_ttl: bool,
+}
+
+impl PromptCaching {
+    pub fn disabled() -> Self {
+        PromptCaching {
+            system: false,
+            tools: false,
+            history: false,
+            extended_ttl: false,
+        }
+    }
+}
+
+impl Default for PromptCaching {
+    fn default() -> Self {
+        PromptCaching {
+            system: true,
+            tools: true,
+            history: true,
+            extended_ttl: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
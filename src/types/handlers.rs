@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 pub type Model<'a> = &'a str;
@@ -9,7 +10,8 @@ pub enum Handlers<'a> {
     Gemini(Model<'a>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum MessageHistory {
     /// Serialized Vec<Message> for Anthropic and OpenAI Completions
     Messages(Value),
@@ -1,40 +1,184 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::types::turn::{Citation, ServerToolRecord};
+
 type Name = String;
 type Message = String;
 type ToolId = String;
 
-#[derive(Debug)]
+/// Bumped whenever a change to `InterfaceEvent` or `LooperToInterfaceMessage`
+/// would break an existing consumer of the JSON wire format.
+pub const INTERFACE_EVENT_SCHEMA_VERSION: u32 = 2;
+
+/// A tool call goes through `ToolCallStarted`, any number of
+/// `ToolCallArgsDelta`s, `ToolCallArgsComplete`, `ToolCallExecuting`, a
+/// `ToolCallInvalid` if the arguments are rejected, and `ToolCallComplete`,
+/// all under the same id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum HandlerToLooperMessage {
+    /// Sent before anything else of a turn, including a resumed one. Only
+    /// sets the ids events are stamped with, it is not forwarded.
+    TurnStarted(TurnStart),
     Assistant(Message),
     Thinking(Message),
     ThinkingComplete,
-    ToolCallPending(ToolId),
-    ToolCallRequest(HandlerToLooperToolCallRequest),
+    /// Sent once per call, as soon as the model named the tool.
+    ToolCallStarted(ToolCallStart),
+    /// Only sent by providers that stream tool arguments.
+    ToolCallArgsDelta(ToolCallArgsDelta),
+    ToolCallArgsComplete(HandlerToLooperToolCallRequest),
+    ToolCallExecuting(HandlerToLooperToolCallRequest),
+    ToolCallInvalid(ToolCallValidationError),
     ToolCallComplete(ToolId),
+    /// The model called a `ServerTool`, `output` is not known yet.
+    ServerToolCall(ServerToolRecord),
+    ServerToolResult(ServerToolRecord),
+    Citation(Citation),
     TurnComplete,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandlerToLooperToolCallRequest {
     pub id: String,
     pub name: String,
     pub args: Value,
 }
 
-#[derive(Debug)]
+/// The id checkpoints are saved under, and the step the turn starts at,
+/// which is past the completed steps of a resumed turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnStart {
+    pub turn_id: String,
+    pub step: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallStart {
+    pub id: ToolId,
+    pub name: Name,
+}
+
+/// The next fragment of a call's raw JSON arguments, for previewing them
+/// while they stream in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallArgsDelta {
+    pub id: ToolId,
+    pub delta: String,
+    /// Best effort parse of all arguments so far, see
+    /// `partial_json::parse_partial`. Large arguments are only parsed
+    /// every so often, this is `None` in between.
+    #[serde(default)]
+    pub partial_args: Option<Value>,
+}
+
+/// Tool arguments generated by the model did not match the tool's declared
+/// parameter schema. The tool is not executed and the errors are returned to
+/// the model as the tool result so it can self-correct.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallValidationError {
+    pub id: ToolId,
+    pub name: Name,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LooperToHandlerToolCallResult {
     pub id: String,
     pub value: Value,
 }
 
-#[derive(Debug)]
+/// Serialized as `{"type": "<snake_case variant>", "data": <payload>}`, with
+/// `data` omitted for variants without a payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum LooperToInterfaceMessage {
     Assistant(Message),
     Thinking(Message),
     ThinkingComplete,
-    ToolCallPending(ToolId),
-    ToolCall(Name),
+    ToolCallStarted(ToolCallStart),
+    ToolCallArgsDelta(ToolCallArgsDelta),
+    ToolCallArgsComplete(HandlerToLooperToolCallRequest),
+    ToolCallExecuting(HandlerToLooperToolCallRequest),
+    ToolCallInvalid(ToolCallValidationError),
     ToolCallComplete(ToolId),
+    ServerToolCall(ServerToolRecord),
+    ServerToolResult(ServerToolRecord),
+    Citation(Citation),
     TurnComplete,
 }
+
+impl LooperToInterfaceMessage {
+    /// The `type` tag this message serializes with.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            LooperToInterfaceMessage::Assistant(_) => "assistant",
+            LooperToInterfaceMessage::Thinking(_) => "thinking",
+            LooperToInterfaceMessage::ThinkingComplete => "thinking_complete",
+            LooperToInterfaceMessage::ToolCallStarted(_) => "tool_call_started",
+            LooperToInterfaceMessage::ToolCallArgsDelta(_) => "tool_call_args_delta",
+            LooperToInterfaceMessage::ToolCallArgsComplete(_) => "tool_call_args_complete",
+            LooperToInterfaceMessage::ToolCallExecuting(_) => "tool_call_executing",
+            LooperToInterfaceMessage::ToolCallInvalid(_) => "tool_call_invalid",
+            LooperToInterfaceMessage::ToolCallComplete(_) => "tool_call_complete",
+            LooperToInterfaceMessage::ServerToolCall(_) => "server_tool_call",
+            LooperToInterfaceMessage::ServerToolResult(_) => "server_tool_result",
+            LooperToInterfaceMessage::Citation(_) => "citation",
+            LooperToInterfaceMessage::TurnComplete => "turn_complete",
+        }
+    }
+}
+
+/// A `LooperToInterfaceMessage` stamped with where and when it happened.
+///
+/// `step` is the zero based index of the model round within the turn, so it
+/// lines up with `TurnResult::steps`. The message's `type`/`data` fields are
+/// flattened into the envelope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceEvent {
+    pub schema_version: u32,
+    pub session_id: String,
+    pub turn_id: String,
+    pub step: usize,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The sub-agent that produced the event, `None` for the top level agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// How many sub-agents deep the producing agent is, 0 at the top level.
+    #[serde(default)]
+    pub depth: usize,
+    #[serde(flatten)]
+    pub message: LooperToInterfaceMessage,
+}
+
+impl InterfaceEvent {
+    pub fn new(
+        session_id: impl Into<String>,
+        turn_id: impl Into<String>,
+        step: usize,
+        message: LooperToInterfaceMessage,
+    ) -> Self {
+        InterfaceEvent {
+            schema_version: INTERFACE_EVENT_SCHEMA_VERSION,
+            session_id: session_id.into(),
+            turn_id: turn_id.into(),
+            step,
+            timestamp_ms: now_ms(),
+            agent_id: None,
+            depth: 0,
+            message,
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch, for `timestamp_ms`.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
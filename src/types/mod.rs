@@ -8,3 +8,6 @@ pub mod handlers;
 pub use handlers::*;
 
 pub mod turn;
+
+pub mod generation;
+pub use generation::*;
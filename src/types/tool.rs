@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LooperToolDefinition {
     pub name: String,
     pub description: String,
@@ -33,3 +34,17 @@ impl LooperToolDefinition {
         self
     }
 }
+
+/// A tool the provider runs on its own side, offered to the model next to
+/// the looper's tools. Its calls never reach `LooperTools`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerTool {
+    /// Anthropic and OpenAI Responses web search, Google Search grounding on
+    /// Gemini.
+    WebSearch,
+    /// Anthropic, OpenAI Responses and Gemini code execution.
+    CodeExecution,
+    /// OpenAI Responses vector store ids or Gemini file search store names.
+    FileSearch { stores: Vec<String> },
+}
@@ -1,10 +1,13 @@
 use super::MessageHistory;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThinkingBlock {
     pub content: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCallRecord {
     pub id: String,
     pub name: String,
@@ -12,14 +15,104 @@ pub struct ToolCallRecord {
     pub result: Value,
 }
 
+/// A call to a `ServerTool` the provider ran while answering. `output` is
+/// whatever the provider reported, in its own format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerToolRecord {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+    #[serde(default)]
+    pub output: Value,
+}
+
+/// A source backing part of a step's text. `start` and `end` are byte
+/// offsets into `TurnStep::text`, set when the provider reports which span the
+/// source supports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    /// The quoted source passage (Anthropic) or the supported answer text
+    /// (Gemini).
+    pub cited_text: Option<String>,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+/// Why the model stopped generating, the same for every provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The answer is finished.
+    #[default]
+    EndTurn,
+    /// The model called tools and waits for their results.
+    ToolUse,
+    /// Cut off at `max_tokens`, the text or tool arguments are incomplete.
+    MaxTokens,
+    StopSequence,
+    /// A long running server tool turn was paused. The loop sends the
+    /// response back as is to let it continue.
+    Paused,
+    /// The model declined to answer.
+    Refusal,
+    /// Blocked by the provider's safety or policy filters.
+    ContentFilter,
+    /// Anything else the provider reports.
+    Other,
+}
+
+/// Token counts of one model request, or summed over a turn. Counts the
+/// provider did not report are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    /// Input tokens served from the provider's prompt cache.
+    pub cache_read_tokens: Option<u64>,
+    /// Input tokens written to the prompt cache, only reported by Anthropic.
+    pub cache_write_tokens: Option<u64>,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        fn sum(total: &mut Option<u64>, count: Option<u64>) {
+            if let Some(count) = count {
+                *total = Some(total.unwrap_or(0) + count);
+            }
+        }
+
+        sum(&mut self.input_tokens, other.input_tokens);
+        sum(&mut self.output_tokens, other.output_tokens);
+        sum(&mut self.cache_read_tokens, other.cache_read_tokens);
+        sum(&mut self.cache_write_tokens, other.cache_write_tokens);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurnStep {
     pub thinking: Vec<ThinkingBlock>,
     pub text: Option<String>,
     pub tool_calls: Vec<ToolCallRecord>,
+    #[serde(default)]
+    pub server_tool_calls: Vec<ServerToolRecord>,
+    #[serde(default)]
+    pub citations: Vec<Citation>,
+    #[serde(default)]
+    pub stop_reason: StopReason,
+    #[serde(default)]
+    pub usage: Usage,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurnResult {
     pub steps: Vec<TurnStep>,
+    /// The text of the last step, joined with the steps it continued after
+    /// they were cut off at `max_tokens`.
     pub final_text: Option<String>,
     pub message_history: MessageHistory,
+    /// The usage of all steps together.
+    #[serde(default)]
+    pub usage: Usage,
 }